@@ -0,0 +1,233 @@
+use bytes::Bytes;
+use reqwest::header::HeaderMap;
+
+use crate::bucket::Bucket;
+use crate::error::OSSError;
+use crate::object::{header_str, PutObjectOptions};
+use crate::types::{HEADER_OSS_HASH_CRC64, HEADER_OSS_REQUEST_ID};
+use crate::Result;
+
+const HEADER_OSS_NEXT_APPEND_POSITION: &str = "x-oss-next-append-position";
+const CODE_POSITION_NOT_EQUAL_TO_LENGTH: &str = "PositionNotEqualToLength";
+
+/// Options of `Bucket::append_object_with_options`.
+#[derive(Debug, Clone, Default)]
+pub struct AppendObjectOptions {
+    object: PutObjectOptions,
+    init_crc: Option<u64>,
+    retry: bool,
+}
+
+impl AppendObjectOptions {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets the content type, user metadata, storage class, ACL... of the object,
+    /// which only take effect on the first append.
+    pub fn object_options(mut self, options: PutObjectOptions) -> Self {
+        self.object = options;
+        self
+    }
+
+    /// The crc64 of the object before this append, which is returned by the previous append.
+    /// The crc64 of the request is verified only if it is present or the position is 0.
+    pub fn init_crc(mut self, crc: u64) -> Self {
+        self.init_crc = Some(crc);
+        self
+    }
+
+    /// Retries the append on transient failures like the other requests, it is disabled by
+    /// default since the append may have been applied before the failure, then the retry fails
+    /// with `PositionNotEqualToLength`.
+    pub fn retry(mut self, retry: bool) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub(crate) fn retry_enabled(&self) -> bool {
+        self.retry
+    }
+
+    pub(crate) fn into_parts(self, position: u64) -> (PutObjectOptions, Option<u64>) {
+        let init_crc = match position {
+            0 => Some(self.init_crc.unwrap_or_default()),
+            _ => self.init_crc,
+        };
+        (self.object, init_crc)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AppendObjectResult {
+    /// The position of the next append, which is also the length of the object.
+    pub next_position: u64,
+    /// The crc64 of the whole object after this append.
+    pub crc64: Option<u64>,
+    pub request_id: String,
+}
+
+impl AppendObjectResult {
+    pub(crate) fn from_headers(headers: &HeaderMap) -> Result<Self> {
+        let next_position = match header_str(headers, HEADER_OSS_NEXT_APPEND_POSITION) {
+            Some(it) => it.parse()?,
+            None => bail!("missing header '{}'", HEADER_OSS_NEXT_APPEND_POSITION),
+        };
+        Ok(AppendObjectResult {
+            next_position,
+            crc64: header_str(headers, HEADER_OSS_HASH_CRC64).and_then(|it| it.parse().ok()),
+            request_id: header_str(headers, HEADER_OSS_REQUEST_ID).unwrap_or_default(),
+        })
+    }
+}
+
+fn is_position_not_equal_to_length(e: &anyhow::Error) -> bool {
+    matches!(
+        e.downcast_ref::<OSSError>(),
+        Some(OSSError::ServiceError(_, code, _, _)) if code == CODE_POSITION_NOT_EQUAL_TO_LENGTH
+    )
+}
+
+/// Appends data to an appendable object continuously, e.g. a rolling log file.
+///
+/// The writer tracks the next position and the crc64 of the object, so that the crc64 of each
+/// append is verified against the whole object. If the position is out of date, e.g. the object
+/// is appended by others, the position is re-read from the object and the append is retried once.
+pub struct AppendWriter {
+    bucket: Bucket,
+    object: String,
+    options: PutObjectOptions,
+    position: u64,
+    crc64: Option<u64>,
+}
+
+impl AppendWriter {
+    pub(crate) fn new(bucket: Bucket, object: String, options: PutObjectOptions) -> AppendWriter {
+        AppendWriter {
+            bucket,
+            object,
+            options,
+            position: 0,
+            crc64: Some(0),
+        }
+    }
+
+    pub fn object(&self) -> &str {
+        &self.object
+    }
+
+    /// The position of the next append.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// The crc64 of the object, `None` if it is unknown.
+    pub fn crc64(&self) -> Option<u64> {
+        self.crc64
+    }
+
+    /// Appends the data to the end of the object.
+    pub async fn append(&mut self, data: impl Into<Bytes>) -> Result<AppendObjectResult> {
+        let data = data.into();
+        match self.try_append(data.clone()).await {
+            Err(e) if is_position_not_equal_to_length(&e) => {
+                self.sync().await?;
+                self.try_append(data).await
+            }
+            res => res,
+        }
+    }
+
+    /// Re-reads the position and crc64 from the object.
+    pub async fn sync(&mut self) -> Result<()> {
+        let meta = self.bucket.head_object(&self.object).await?;
+        info!(
+            "sync append position of '{}': {} -> {}",
+            self.object, self.position, meta.content_length
+        );
+        self.position = meta.content_length;
+        self.crc64 = meta.crc64;
+        Ok(())
+    }
+
+    async fn try_append(&mut self, data: Bytes) -> Result<AppendObjectResult> {
+        let mut options = AppendObjectOptions::new().object_options(self.options.clone());
+        if let Some(crc) = self.crc64 {
+            options = options.init_crc(crc);
+        }
+        let res = self
+            .bucket
+            .append_object_with_options(&self.object, self.position, data, options)
+            .await?;
+        self.position = res.next_position;
+        self.crc64 = res.crc64;
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod test_super {
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+
+    #[test]
+    fn test_from_headers() {
+        let mut headers = HeaderMap::new();
+        assert!(AppendObjectResult::from_headers(&headers).is_err());
+
+        headers.insert(
+            HEADER_OSS_NEXT_APPEND_POSITION,
+            HeaderValue::from_static("11"),
+        );
+        headers.insert(HEADER_OSS_HASH_CRC64, HeaderValue::from_static("12345"));
+        let res = AppendObjectResult::from_headers(&headers).unwrap();
+        assert_eq!(11, res.next_position);
+        assert_eq!(Some(12345), res.crc64);
+    }
+
+    #[test]
+    fn test_init_crc() {
+        assert_eq!(Some(0), AppendObjectOptions::new().into_parts(0).1);
+        assert_eq!(None, AppendObjectOptions::new().into_parts(10).1);
+        assert_eq!(
+            Some(1),
+            AppendObjectOptions::new().init_crc(1).into_parts(10).1
+        );
+    }
+
+    #[test]
+    fn test_position_not_equal_to_length() {
+        let e: anyhow::Error = OSSError::ServiceError(
+            409,
+            CODE_POSITION_NOT_EQUAL_TO_LENGTH.into(),
+            "".into(),
+            "".into(),
+        )
+        .into();
+        assert!(is_position_not_equal_to_length(&e));
+        assert!(!is_position_not_equal_to_length(&anyhow!("oops")));
+    }
+
+    #[tokio::test]
+    async fn test_retry() {
+        let (endpoint, requests) = crate::test_support::serve(|_| (503, "".into())).await;
+        let bucket = crate::test_support::bucket(&endpoint);
+
+        // the append may have been applied, so it is not retried by default
+        let e = bucket.append_object("a.txt", 0, "hello").await.unwrap_err();
+        assert!(matches!(
+            e.downcast_ref::<OSSError>(),
+            Some(OSSError::ServiceError(503, ..))
+        ));
+        assert_eq!(1, requests.lock().unwrap().len());
+
+        requests.lock().unwrap().clear();
+        let options = AppendObjectOptions::new().retry(true);
+        bucket
+            .append_object_with_options("a.txt", 0, "hello", options)
+            .await
+            .unwrap_err();
+        assert_eq!(3, requests.lock().unwrap().len());
+    }
+}
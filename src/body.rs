@@ -0,0 +1,344 @@
+use std::fmt::Debug;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use bytes::Bytes;
+use futures::{stream, Stream, StreamExt, TryStreamExt};
+use tempfile::TempPath;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio_util::io::ReaderStream;
+
+use crate::crc64;
+use crate::timeout::Progress;
+use crate::util;
+use crate::Result;
+
+const MD5_BUFFER_SIZE: usize = 64 * 1024;
+// an in-memory body is sent in chunks of this size, so that the progress can be tracked
+const PROGRESS_CHUNK_SIZE: usize = 256 * 1024;
+
+enum Inner {
+    Bytes(Bytes),
+    File(PathBuf),
+    // a reader spooled to disk, which is removed when the last clone is dropped
+    TempFile(Arc<TempPath>),
+    Reader(Box<dyn AsyncRead + Send + Unpin>),
+}
+
+/// The payload of an upload request: in-memory bytes, a local file or any `AsyncRead`.
+pub struct Body {
+    inner: Inner,
+}
+
+impl Body {
+    /// Creates a body which streams the content of a local file.
+    pub fn from_file(path: impl AsRef<Path>) -> Body {
+        Body {
+            inner: Inner::File(path.as_ref().to_path_buf()),
+        }
+    }
+
+    /// Creates a body which streams from an `AsyncRead`, the length is unknown until EOF.
+    pub fn from_reader<R>(reader: R) -> Body
+    where
+        R: AsyncRead + Send + Unpin + 'static,
+    {
+        Body {
+            inner: Inner::Reader(Box::new(reader)),
+        }
+    }
+
+    pub(crate) fn empty() -> Body {
+        Body {
+            inner: Inner::Bytes(Bytes::new()),
+        }
+    }
+
+    pub(crate) fn as_bytes(&self) -> Option<&Bytes> {
+        match &self.inner {
+            Inner::Bytes(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// Returns the length of the body, `None` if it cannot be known before sending.
+    pub(crate) fn len(&self) -> Result<Option<u64>> {
+        match &self.inner {
+            Inner::Bytes(b) => Ok(Some(b.len() as u64)),
+            Inner::File(path) => Ok(Some(std::fs::metadata(path)?.len())),
+            Inner::TempFile(path) => Ok(Some(std::fs::metadata(path.as_ref())?.len())),
+            Inner::Reader(_) => Ok(None),
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        match &self.inner {
+            Inner::Bytes(b) => b.is_empty(),
+            _ => false,
+        }
+    }
+
+    /// Clones the body so that the request can be sent again, `None` if it is a reader which
+    /// can only be read once.
+    pub(crate) fn try_clone(&self) -> Option<Body> {
+        let inner = match &self.inner {
+            Inner::Bytes(b) => Inner::Bytes(b.clone()),
+            Inner::File(path) => Inner::File(path.clone()),
+            Inner::TempFile(path) => Inner::TempFile(path.clone()),
+            Inner::Reader(_) => return None,
+        };
+        Some(Body { inner })
+    }
+
+    /// Converts to the request body, the crc64 of the sent bytes is accumulated into `crc` if present,
+    /// and the sent chunks are tracked by `progress`.
+    pub(crate) async fn into_reqwest(
+        self,
+        crc: Option<Arc<AtomicU64>>,
+        progress: &Progress,
+    ) -> Result<reqwest::Body> {
+        match self.inner {
+            Inner::Bytes(b) => {
+                if let Some(crc) = crc {
+                    let v = crc.load(Ordering::SeqCst);
+                    crc.store(crc64::update(v, &b), Ordering::SeqCst);
+                }
+                let chunks: Vec<_> = (0..b.len())
+                    .step_by(PROGRESS_CHUNK_SIZE)
+                    .map(|i| Ok(b.slice(i..(i + PROGRESS_CHUNK_SIZE).min(b.len()))))
+                    .collect();
+                Ok(reqwest::Body::wrap_stream(
+                    progress.wrap(stream::iter(chunks)),
+                ))
+            }
+            Inner::File(path) => {
+                let file = tokio::fs::File::open(&path).await?;
+                Ok(Self::wrap_stream(ReaderStream::new(file), crc, progress))
+            }
+            Inner::TempFile(path) => {
+                let file = tokio::fs::File::open(path.as_ref()).await?;
+                let stream = TempFileStream {
+                    inner: ReaderStream::new(file),
+                    _path: path,
+                };
+                Ok(Self::wrap_stream(stream, crc, progress))
+            }
+            Inner::Reader(r) => Ok(Self::wrap_stream(ReaderStream::new(r), crc, progress)),
+        }
+    }
+
+    /// Computes the base64 encoded MD5 of the body.
+    ///
+    /// A reader is consumed to compute the digest, its content is kept in memory if it is not
+    /// larger than `threshold`, otherwise it is spooled to a temp file.
+    pub(crate) async fn content_md5(&mut self, threshold: u64) -> Result<String> {
+        let digest = match &mut self.inner {
+            Inner::Bytes(b) => return Ok(util::content_md5(b)),
+            Inner::File(path) => Self::md5_of_file(path).await?,
+            Inner::TempFile(path) => Self::md5_of_file(path).await?,
+            Inner::Reader(r) => {
+                let mut ctx = md5::Context::new();
+                let mut memory = Vec::new();
+                let mut spooled: Option<(tokio::fs::File, TempPath)> = None;
+                let mut buf = vec![0u8; MD5_BUFFER_SIZE];
+                loop {
+                    let n = r.read(&mut buf).await?;
+                    if n == 0 {
+                        break;
+                    }
+                    ctx.consume(&buf[..n]);
+                    if spooled.is_none() && (memory.len() + n) as u64 > threshold {
+                        let (file, path) = tempfile::NamedTempFile::new()?.into_parts();
+                        let mut file = tokio::fs::File::from_std(file);
+                        file.write_all(&memory).await?;
+                        memory = Vec::new();
+                        spooled = Some((file, path));
+                    }
+                    match &mut spooled {
+                        Some((file, _)) => file.write_all(&buf[..n]).await?,
+                        None => memory.extend_from_slice(&buf[..n]),
+                    }
+                }
+                self.inner = match spooled {
+                    Some((mut file, path)) => {
+                        file.flush().await?;
+                        Inner::TempFile(Arc::new(path))
+                    }
+                    None => Inner::Bytes(memory.into()),
+                };
+                ctx.compute()
+            }
+        };
+        Ok(STANDARD.encode(digest.0))
+    }
+
+    async fn md5_of_file(path: &Path) -> Result<md5::Digest> {
+        let mut file = tokio::fs::File::open(path).await?;
+        let mut ctx = md5::Context::new();
+        let mut buf = vec![0u8; MD5_BUFFER_SIZE];
+        loop {
+            let n = file.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            ctx.consume(&buf[..n]);
+        }
+        Ok(ctx.compute())
+    }
+
+    fn wrap_stream<S>(stream: S, crc: Option<Arc<AtomicU64>>, progress: &Progress) -> reqwest::Body
+    where
+        S: futures::Stream<Item = std::io::Result<Bytes>> + Send + 'static,
+    {
+        match crc {
+            Some(crc) => {
+                reqwest::Body::wrap_stream(progress.wrap(stream.inspect_ok(move |chunk| {
+                    let v = crc.load(Ordering::SeqCst);
+                    crc.store(crc64::update(v, chunk), Ordering::SeqCst);
+                })))
+            }
+            None => reqwest::Body::wrap_stream(progress.wrap(stream)),
+        }
+    }
+}
+
+/// Reads a temp file, which is kept until the stream is dropped.
+struct TempFileStream {
+    inner: ReaderStream<tokio::fs::File>,
+    _path: Arc<TempPath>,
+}
+
+impl Stream for TempFileStream {
+    type Item = std::io::Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.poll_next_unpin(cx)
+    }
+}
+
+impl Default for Body {
+    fn default() -> Self {
+        Body::empty()
+    }
+}
+
+impl Debug for Body {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.inner {
+            Inner::Bytes(b) => f.debug_tuple("Bytes").field(&b.len()).finish(),
+            Inner::File(path) => f.debug_tuple("File").field(path).finish(),
+            Inner::TempFile(path) => f.debug_tuple("TempFile").field(path).finish(),
+            Inner::Reader(_) => f.debug_tuple("Reader").finish(),
+        }
+    }
+}
+
+impl From<Bytes> for Body {
+    fn from(value: Bytes) -> Self {
+        Body {
+            inner: Inner::Bytes(value),
+        }
+    }
+}
+
+impl From<Vec<u8>> for Body {
+    fn from(value: Vec<u8>) -> Self {
+        Bytes::from(value).into()
+    }
+}
+
+impl From<&'static [u8]> for Body {
+    fn from(value: &'static [u8]) -> Self {
+        Bytes::from_static(value).into()
+    }
+}
+
+impl From<String> for Body {
+    fn from(value: String) -> Self {
+        Bytes::from(value).into()
+    }
+}
+
+impl From<&'static str> for Body {
+    fn from(value: &'static str) -> Self {
+        Bytes::from_static(value.as_bytes()).into()
+    }
+}
+
+#[cfg(test)]
+mod test_super {
+    use super::*;
+
+    const HELLO_MD5: &str = "XrY7u+Ae7tCTyyK7j1rNww==";
+
+    #[tokio::test]
+    async fn test_content_md5() {
+        let mut body = Body::from("hello world");
+        assert_eq!(HELLO_MD5, body.content_md5(0).await.unwrap());
+
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), b"hello world").unwrap();
+        let mut body = Body::from_file(file.path());
+        assert_eq!(HELLO_MD5, body.content_md5(0).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_content_md5_of_reader() {
+        // small reader is buffered in memory
+        let mut body = Body::from_reader(&b"hello world"[..]);
+        assert_eq!(HELLO_MD5, body.content_md5(1024).await.unwrap());
+        assert_eq!(Some(11), body.len().unwrap());
+        assert!(body.as_bytes().is_some());
+
+        // large reader is spooled to a temp file
+        let mut body = Body::from_reader(&b"hello world"[..]);
+        assert_eq!(HELLO_MD5, body.content_md5(5).await.unwrap());
+        assert_eq!(Some(11), body.len().unwrap());
+        assert!(body.as_bytes().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_try_clone() {
+        assert!(Body::from("hello world").try_clone().is_some());
+        assert!(Body::from_reader(&b"hello world"[..]).try_clone().is_none());
+
+        // a spooled reader can be replayed
+        let mut body = Body::from_reader(&b"hello world"[..]);
+        body.content_md5(5).await.unwrap();
+        let cloned = body.try_clone().unwrap();
+        drop(body);
+        assert_eq!(Some(11), cloned.len().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_into_reqwest() {
+        // the body is sent only when the stream ends, even if it is small
+        let progress = Progress::new(true);
+        let body = Body::from("hello world")
+            .into_reqwest(None, &progress)
+            .await
+            .unwrap();
+        assert!(body.as_bytes().is_none());
+        assert!(progress.is_sending());
+    }
+
+    #[tokio::test]
+    async fn test_temp_file_lifetime() {
+        let mut body = Body::from_reader(&b"hello world"[..]);
+        body.content_md5(5).await.unwrap();
+        let path = match &body.inner {
+            Inner::TempFile(it) => it.to_path_buf(),
+            _ => panic!("the reader is not spooled"),
+        };
+
+        // the temp file is removed when the request body is dropped
+        let req_body = body.into_reqwest(None, &Progress::new(true)).await.unwrap();
+        assert!(path.exists());
+        drop(req_body);
+        assert!(!path.exists());
+    }
+}
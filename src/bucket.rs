@@ -1,4 +1,27 @@
+use std::path::Path;
+use std::time::Duration;
+
+use futures::{StreamExt, TryStreamExt};
+
+use crate::append::{AppendObjectOptions, AppendObjectResult, AppendWriter};
+use crate::body::Body;
 use crate::client::Client;
+use crate::copy::{self, CopyObjectOptions, CopyObjectResult};
+use crate::delete::{self, DeleteObjectsResult, ObjectIdentifier};
+use crate::download::{self, DownloadFileOptions};
+use crate::list::{ListEntry, ListObjectsV2Options, ListObjectsV2Result, ListStream};
+use crate::multipart::{
+    self, CompleteMultipartUploadOptions, CompleteMultipartUploadResult,
+    InitiateMultipartUploadOptions, InitiateMultipartUploadResult, ListMultipartUploadsOptions,
+    ListMultipartUploadsResult, ListPartsOptions, ListPartsResult, PartInfo, UploadId,
+};
+use crate::object::{
+    GetObjectOptions, GetObjectOutput, ObjectMeta, PutObjectOptions, PutObjectResult,
+};
+use crate::post::{PostForm, PostPolicy};
+use crate::presign::PresignOptions;
+use crate::types::{Headers, Params, RequestOptions};
+use crate::upload::{self, UploadFileOptions};
 use crate::util;
 use crate::Result;
 
@@ -13,26 +36,665 @@ impl Bucket {
         Bucket { client, name }
     }
 
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the handle of another bucket which shares the client.
+    pub(crate) fn sibling(&self, name: &str) -> Result<Bucket> {
+        util::check_bucket_name(name)?;
+        Ok(Bucket::new(self.client.clone(), name.into()))
+    }
+
+    #[inline]
+    pub(crate) fn crc_enabled(&self) -> bool {
+        self.client.config.enable_crc
+    }
+
     pub async fn get_object(&self, object: impl AsRef<str>) -> Result<Vec<u8>> {
+        let b = self.get_object_stream(object).await?.bytes().await?;
+        Ok(b.to_vec())
+    }
+
+    pub async fn get_object_with_options(
+        &self,
+        object: impl AsRef<str>,
+        options: GetObjectOptions,
+    ) -> Result<Vec<u8>> {
+        let b = self
+            .get_object_stream_with_options(object, options)
+            .await?
+            .bytes()
+            .await?;
+        Ok(b.to_vec())
+    }
+
+    /// Gets an object without buffering, the content can be consumed as a stream or an `AsyncRead`.
+    pub async fn get_object_stream(&self, object: impl AsRef<str>) -> Result<GetObjectOutput> {
+        self.get_object_stream_with_options(object, Default::default())
+            .await
+    }
+
+    pub async fn get_object_stream_with_options(
+        &self,
+        object: impl AsRef<str>,
+        options: GetObjectOptions,
+    ) -> Result<GetObjectOutput> {
         let object = object.as_ref();
-        self.do_request(reqwest::Method::GET, object).await
+        let request_options = self.transfer_options(None, options.timeout_override());
+        let (params, headers) = options.into_parts();
+        let resp = self
+            .do_request_with_options(
+                reqwest::Method::GET,
+                object,
+                params,
+                Some(headers),
+                Body::empty(),
+                request_options,
+            )
+            .await?;
+        let stall = self.client.config.http_timeout.read_write;
+        Ok(GetObjectOutput::new(resp, self.crc_enabled(), stall))
     }
 
-    #[inline]
-    async fn do_request(&self, method: reqwest::Method, object: &str) -> Result<Vec<u8>> {
+    pub async fn put_object(
+        &self,
+        object: impl AsRef<str>,
+        body: impl Into<Body>,
+    ) -> Result<PutObjectResult> {
+        self.put_object_with_options(object, body, Default::default())
+            .await
+    }
+
+    pub async fn put_object_with_options(
+        &self,
+        object: impl AsRef<str>,
+        body: impl Into<Body>,
+        options: PutObjectOptions,
+    ) -> Result<PutObjectResult> {
+        let object = object.as_ref();
+        let request_options = self.transfer_options(Some(0), options.timeout_override());
+        let headers = options.into_headers();
+        let resp = self
+            .do_request_with_options(
+                reqwest::Method::PUT,
+                object,
+                None,
+                Some(headers),
+                body.into(),
+                request_options,
+            )
+            .await?;
+        Ok(PutObjectResult::from_headers(resp.headers()))
+    }
+
+    /// Appends the body to an appendable object at the position, which should be equal to the
+    /// length of the object. The object is created if the position is 0.
+    pub async fn append_object(
+        &self,
+        object: impl AsRef<str>,
+        position: u64,
+        body: impl Into<Body>,
+    ) -> Result<AppendObjectResult> {
+        self.append_object_with_options(object, position, body, Default::default())
+            .await
+    }
+
+    pub async fn append_object_with_options(
+        &self,
+        object: impl AsRef<str>,
+        position: u64,
+        body: impl Into<Body>,
+        options: AppendObjectOptions,
+    ) -> Result<AppendObjectResult> {
+        let object = object.as_ref();
+        let retry = options.retry_enabled();
+        let (options, init_crc) = options.into_parts(position);
+        let request_options = RequestOptions {
+            idempotent: retry,
+            ..self.transfer_options(init_crc, options.timeout_override())
+        };
+        let mut params = Params::new();
+        params.insert("append".into(), None);
+        params.insert("position".into(), Some(position.to_string()));
+        let resp = self
+            .do_request_with_options(
+                reqwest::Method::POST,
+                object,
+                Some(params),
+                Some(options.into_headers()),
+                body.into(),
+                request_options,
+            )
+            .await?;
+        AppendObjectResult::from_headers(resp.headers())
+    }
+
+    /// Creates a writer which appends to the object from the beginning, the position is
+    /// synchronized automatically if the object already exists.
+    pub fn append_writer(&self, object: impl Into<String>) -> AppendWriter {
+        self.append_writer_with_options(object, Default::default())
+    }
+
+    pub fn append_writer_with_options(
+        &self,
+        object: impl Into<String>,
+        options: PutObjectOptions,
+    ) -> AppendWriter {
+        AppendWriter::new(self.clone(), object.into(), options)
+    }
+
+    /// Copies an object in the bucket, the source can be in another bucket of the same region,
+    /// see `CopyObjectOptions::source_bucket`. A source larger than 1GB is copied by
+    /// multipart copy automatically.
+    pub async fn copy_object(
+        &self,
+        source_object: impl AsRef<str>,
+        object: impl AsRef<str>,
+    ) -> Result<CopyObjectResult> {
+        self.copy_object_with_options(source_object, object, Default::default())
+            .await
+    }
+
+    pub async fn copy_object_with_options(
+        &self,
+        source_object: impl AsRef<str>,
+        object: impl AsRef<str>,
+        options: CopyObjectOptions,
+    ) -> Result<CopyObjectResult> {
+        let (source_object, object) = (source_object.as_ref(), object.as_ref());
+        let source = self.sibling(options.source_bucket_name(&self.name))?;
+        let resp = source
+            .do_request(
+                reqwest::Method::HEAD,
+                source_object,
+                options.source_version_params(),
+                None,
+                Body::empty(),
+            )
+            .await?;
+        let source_headers = resp.headers().clone();
+        let size = ObjectMeta::from_headers(&source_headers).content_length;
+        if size > copy::MAX_COPY_OBJECT_SIZE {
+            return copy::multipart_copy(
+                self,
+                &source,
+                &source_headers,
+                source_object,
+                object,
+                options,
+            )
+            .await;
+        }
+
+        let headers = options.into_headers(&self.name, source_object);
+        let resp = self
+            .do_request(
+                reqwest::Method::PUT,
+                object,
+                None,
+                Some(headers),
+                Body::empty(),
+            )
+            .await?;
+        let headers = resp.headers().clone();
+        let b = resp.bytes().await?;
+        CopyObjectResult::try_from_response(&headers, &b)
+    }
+
+    /// Gets all the metadata of an object without returning its content.
+    pub async fn head_object(&self, object: impl AsRef<str>) -> Result<ObjectMeta> {
+        let object = object.as_ref();
+        let resp = self
+            .do_request(reqwest::Method::HEAD, object, None, None, Body::empty())
+            .await?;
+        Ok(ObjectMeta::from_headers(resp.headers()))
+    }
+
+    /// Gets the basic metadata of an object (ETag, size and last modified time),
+    /// which is lighter than `head_object`.
+    pub async fn get_object_meta(&self, object: impl AsRef<str>) -> Result<ObjectMeta> {
+        let object = object.as_ref();
+        let mut params = Params::new();
+        params.insert("objectMeta".into(), None);
+        let resp = self
+            .do_request(
+                reqwest::Method::HEAD,
+                object,
+                Some(params),
+                None,
+                Body::empty(),
+            )
+            .await?;
+        Ok(ObjectMeta::from_headers(resp.headers()))
+    }
+
+    pub async fn delete_object(&self, object: impl AsRef<str>) -> Result<()> {
+        let object = object.as_ref();
+        self.do_request(reqwest::Method::DELETE, object, None, None, Body::empty())
+            .await?;
+        Ok(())
+    }
+
+    /// Deletes at most 1000 objects in one request. In quiet mode only the failed ones are returned.
+    pub async fn delete_objects<I, T>(&self, objects: I, quiet: bool) -> Result<DeleteObjectsResult>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<ObjectIdentifier>,
+    {
+        let objects: Vec<ObjectIdentifier> = objects.into_iter().map(Into::into).collect();
+        if objects.is_empty() {
+            return Ok(Default::default());
+        }
+        if objects.len() > delete::MAX_DELETE_OBJECTS {
+            bail!(
+                "cannot delete more than {} objects in one request, now is {}",
+                delete::MAX_DELETE_OBJECTS,
+                objects.len()
+            );
+        }
+
+        let xml = delete::build_delete_xml(&objects, quiet);
+
+        let mut params = Params::new();
+        params.insert("delete".into(), None);
+        let mut headers = Headers::new();
+        headers.insert("content-md5".into(), util::content_md5(xml.as_bytes()));
+        headers.insert("content-type".into(), "application/xml".into());
+
+        // deleting the objects again has the same effect, so it can be retried
+        let request_options = RequestOptions {
+            idempotent: true,
+            ..Default::default()
+        };
+        let resp = self
+            .do_request_with_options(
+                reqwest::Method::POST,
+                "",
+                Some(params),
+                Some(headers),
+                xml.into(),
+                request_options,
+            )
+            .await?;
+        let b = resp.bytes().await?;
+        DeleteObjectsResult::try_from_xml(&b)
+    }
+
+    /// Deletes all the objects which start with the prefix, returns the number of deleted objects.
+    /// At most `concurrency` batch requests are running at the same time.
+    pub async fn delete_prefix(&self, prefix: impl AsRef<str>, concurrency: usize) -> Result<u64> {
+        let prefix = prefix.as_ref();
+        if prefix.is_empty() {
+            bail!("prefix cannot be empty when deleting by prefix!");
+        }
+
+        let options = ListObjectsV2Options::new()
+            .prefix(prefix)
+            .max_keys(delete::MAX_DELETE_OBJECTS as u32);
+
+        self.list_all(options)
+            .try_filter_map(|entry| async move {
+                match entry {
+                    ListEntry::Object(it) => Ok(Some(it.key)),
+                    ListEntry::CommonPrefix(_) => Ok(None),
+                }
+            })
+            .try_chunks(delete::MAX_DELETE_OBJECTS)
+            .map_err(|e| e.1)
+            .map_ok(|keys| async move {
+                let total = keys.len() as u64;
+                let res = self.delete_objects(keys, true).await?;
+                if let Some(e) = res.errors.first() {
+                    bail!("delete object '{}' failed: {} {}", e.key, e.code, e.message);
+                }
+                Ok(total)
+            })
+            .try_buffer_unordered(concurrency.max(1))
+            .try_fold(0, |acc, n| futures::future::ready(Ok(acc + n)))
+            .await
+    }
+
+    /// Lists one page of the objects, use `continuation_token` of the options to get the next page.
+    pub async fn list_objects_v2(
+        &self,
+        options: ListObjectsV2Options,
+    ) -> Result<ListObjectsV2Result> {
+        let resp = self
+            .do_request(
+                reqwest::Method::GET,
+                "",
+                Some(options.to_params()),
+                None,
+                Body::empty(),
+            )
+            .await?;
+        let b = resp.bytes().await?;
+        ListObjectsV2Result::try_from_xml(&b)
+    }
+
+    /// Lists all the objects and common prefixes as a stream, the pages are fetched on demand.
+    pub fn list_all(&self, options: ListObjectsV2Options) -> ListStream {
+        let bucket = self.clone();
+        let pages = futures::stream::try_unfold(Some(options), move |options| {
+            let bucket = bucket.clone();
+            async move {
+                let options = match options {
+                    Some(it) => it,
+                    None => return Ok(None),
+                };
+                let res = bucket.list_objects_v2(options.clone()).await?;
+                let next = match &res.next_continuation_token {
+                    Some(it) if res.is_truncated => Some(options.continuation_token(it)),
+                    _ => None,
+                };
+
+                let entries = res
+                    .common_prefixes
+                    .into_iter()
+                    .map(ListEntry::CommonPrefix)
+                    .chain(res.contents.into_iter().map(ListEntry::Object))
+                    .map(Ok::<_, anyhow::Error>);
+                Ok::<_, anyhow::Error>(Some((futures::stream::iter(entries), next)))
+            }
+        });
+        pages.try_flatten().boxed()
+    }
+
+    pub async fn initiate_multipart_upload(&self, object: impl AsRef<str>) -> Result<UploadId> {
+        self.initiate_multipart_upload_with_options(object, Default::default())
+            .await
+    }
+
+    pub async fn initiate_multipart_upload_with_options(
+        &self,
+        object: impl AsRef<str>,
+        options: InitiateMultipartUploadOptions,
+    ) -> Result<UploadId> {
+        let object = object.as_ref();
+        let (params, headers) = options.into_parts();
+        let resp = self
+            .do_request(
+                reqwest::Method::POST,
+                object,
+                Some(params),
+                Some(headers),
+                Body::empty(),
+            )
+            .await?;
+        let b = resp.bytes().await?;
+        let res = InitiateMultipartUploadResult::try_from_xml(&b)?;
+        Ok(UploadId::new(res.upload_id))
+    }
+
+    /// Uploads a part, the part number is between [1-10000]. Each part except the last one
+    /// should be at least 100KB.
+    pub async fn upload_part(
+        &self,
+        object: impl AsRef<str>,
+        upload_id: &UploadId,
+        part_number: u32,
+        body: impl Into<Body>,
+    ) -> Result<PartInfo> {
+        multipart::check_part_number(part_number)?;
+
+        let object = object.as_ref();
+        let mut params = Params::new();
+        params.insert("partNumber".into(), Some(part_number.to_string()));
+        params.insert("uploadId".into(), Some(upload_id.to_string()));
+        let resp = self
+            .do_request_with_options(
+                reqwest::Method::PUT,
+                object,
+                Some(params),
+                None,
+                body.into(),
+                self.transfer_options(Some(0), None),
+            )
+            .await?;
+        Ok(PartInfo::from_headers(part_number, resp.headers()))
+    }
+
+    /// Copies a range of the source object as a part, the whole object is copied if the range
+    /// is `None`. The range is inclusive, e.g. `(0, 99)` is the first 100 bytes.
+    /// The source bucket, version and conditions are taken from the options.
+    pub async fn upload_part_copy(
+        &self,
+        object: impl AsRef<str>,
+        upload_id: &UploadId,
+        part_number: u32,
+        source_object: impl AsRef<str>,
+        range: Option<(u64, u64)>,
+        options: &CopyObjectOptions,
+    ) -> Result<PartInfo> {
+        multipart::check_part_number(part_number)?;
+
+        let object = object.as_ref();
+        let mut params = Params::new();
+        params.insert("partNumber".into(), Some(part_number.to_string()));
+        params.insert("uploadId".into(), Some(upload_id.to_string()));
+        let mut headers = options.source_headers(&self.name, source_object.as_ref());
+        if let Some((start, end)) = range {
+            headers.insert(
+                "x-oss-copy-source-range".into(),
+                format!("bytes={}-{}", start, end),
+            );
+        }
+        let resp = self
+            .do_request(
+                reqwest::Method::PUT,
+                object,
+                Some(params),
+                Some(headers),
+                Body::empty(),
+            )
+            .await?;
+        let b = resp.bytes().await?;
+        copy::part_info_from_response(part_number, &b)
+    }
+
+    pub async fn complete_multipart_upload(
+        &self,
+        object: impl AsRef<str>,
+        upload_id: &UploadId,
+        parts: &[PartInfo],
+    ) -> Result<CompleteMultipartUploadResult> {
+        self.complete_multipart_upload_with_options(object, upload_id, parts, Default::default())
+            .await
+    }
+
+    pub async fn complete_multipart_upload_with_options(
+        &self,
+        object: impl AsRef<str>,
+        upload_id: &UploadId,
+        parts: &[PartInfo],
+        options: CompleteMultipartUploadOptions,
+    ) -> Result<CompleteMultipartUploadResult> {
+        let object = object.as_ref();
+        let xml = multipart::build_complete_xml(parts);
+
+        let mut params = Params::new();
+        params.insert("uploadId".into(), Some(upload_id.to_string()));
+        params.insert("encoding-type".into(), Some("url".into()));
+        let mut headers = Headers::new();
+        headers.insert("content-type".into(), "application/xml".into());
+
+        let request_options = RequestOptions {
+            idempotent: options.retry_enabled(),
+            ..Default::default()
+        };
+        let resp = self
+            .do_request_with_options(
+                reqwest::Method::POST,
+                object,
+                Some(params),
+                Some(headers),
+                xml.into(),
+                request_options,
+            )
+            .await?;
+        let headers = resp.headers().clone();
+        let b = resp.bytes().await?;
+        CompleteMultipartUploadResult::try_from_response(&headers, &b)
+    }
+
+    pub async fn abort_multipart_upload(
+        &self,
+        object: impl AsRef<str>,
+        upload_id: &UploadId,
+    ) -> Result<()> {
+        let object = object.as_ref();
+        let mut params = Params::new();
+        params.insert("uploadId".into(), Some(upload_id.to_string()));
+        self.do_request(
+            reqwest::Method::DELETE,
+            object,
+            Some(params),
+            None,
+            Body::empty(),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Lists one page of the uploaded parts, use `next_part_number_marker` to get the next page.
+    pub async fn list_parts(
+        &self,
+        object: impl AsRef<str>,
+        upload_id: &UploadId,
+        options: ListPartsOptions,
+    ) -> Result<ListPartsResult> {
+        let object = object.as_ref();
+        let resp = self
+            .do_request(
+                reqwest::Method::GET,
+                object,
+                Some(options.into_params(upload_id)),
+                None,
+                Body::empty(),
+            )
+            .await?;
+        let b = resp.bytes().await?;
+        ListPartsResult::try_from_xml(&b)
+    }
+
+    /// Lists one page of the running multipart uploads, use `next_key_marker` and
+    /// `next_upload_id_marker` to get the next page.
+    pub async fn list_multipart_uploads(
+        &self,
+        options: ListMultipartUploadsOptions,
+    ) -> Result<ListMultipartUploadsResult> {
+        let resp = self
+            .do_request(
+                reqwest::Method::GET,
+                "",
+                Some(options.into_params()),
+                None,
+                Body::empty(),
+            )
+            .await?;
+        let b = resp.bytes().await?;
+        ListMultipartUploadsResult::try_from_xml(&b)
+    }
+
+    /// Uploads a local file with multipart upload, the parts are uploaded in parallel.
+    /// If the checkpoint is enabled, a failed upload will be resumed on the next call.
+    pub async fn upload_file(
+        &self,
+        object: impl AsRef<str>,
+        file: impl AsRef<Path>,
+        options: UploadFileOptions,
+    ) -> Result<CompleteMultipartUploadResult> {
+        upload::upload_file(self, object.as_ref(), file.as_ref(), options).await
+    }
+
+    /// Downloads an object to a local file, the ranges are downloaded in parallel into a
+    /// temporary file, which is renamed to the target file when completed.
+    /// If the checkpoint is enabled, a failed download will be resumed on the next call.
+    pub async fn download_file(
+        &self,
+        object: impl AsRef<str>,
+        file: impl AsRef<Path>,
+        options: DownloadFileOptions,
+    ) -> Result<ObjectMeta> {
+        download::download_file(self, object.as_ref(), file.as_ref(), options).await
+    }
+
+    /// Generates a presigned URL of the object, which can be used without credentials until
+    /// it expires, e.g. `GET` for downloading and `PUT` for uploading from browsers.
+    pub async fn presign_url(
+        &self,
+        method: reqwest::Method,
+        object: impl AsRef<str>,
+        expires: Duration,
+        options: PresignOptions,
+    ) -> Result<String> {
         util::check_bucket_name(&self.name)?;
 
+        let (params, headers) = options.into_parts()?;
         self.client
             .conn
-            .execute(
+            .presign(
                 method,
                 &self.name,
-                object,
-                None,
-                None,
-                Default::default(),
-                0,
+                object.as_ref(),
+                params,
+                headers,
+                chrono::Utc::now(),
+                expires,
             )
             .await
     }
+
+    /// Signs the policy of `PostObject`, returns the url and the form fields which can be used
+    /// by browsers to upload directly.
+    pub async fn post_policy_form(&self, policy: PostPolicy) -> Result<PostForm> {
+        util::check_bucket_name(&self.name)?;
+        policy
+            .into_form(&self.client.conn, &self.name, chrono::Utc::now())
+            .await
+    }
+
+    #[inline]
+    pub(crate) async fn do_request(
+        &self,
+        method: reqwest::Method,
+        object: &str,
+        params: Option<Params>,
+        headers: Option<Headers>,
+        body: Body,
+    ) -> Result<reqwest::Response> {
+        self.do_request_with_options(method, object, params, headers, body, Default::default())
+            .await
+    }
+
+    /// The options of a request which transfers the object content, the crc64 of the body is
+    /// checked against the server if `init_crc` is present, and the timeout defaults to
+    /// `HttpTimeout::long`.
+    fn transfer_options(&self, init_crc: Option<u64>, timeout: Option<Duration>) -> RequestOptions {
+        RequestOptions {
+            init_crc,
+            timeout,
+            transfer: true,
+            ..Default::default()
+        }
+    }
+
+    #[inline]
+    async fn do_request_with_options(
+        &self,
+        method: reqwest::Method,
+        object: &str,
+        params: Option<Params>,
+        headers: Option<Headers>,
+        body: Body,
+        options: RequestOptions,
+    ) -> Result<reqwest::Response> {
+        util::check_bucket_name(&self.name)?;
+
+        self.client
+            .conn
+            .execute(method, &self.name, object, params, headers, body, options)
+            .await
+    }
 }
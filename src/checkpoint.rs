@@ -0,0 +1,97 @@
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::Result;
+
+/// The checkpoint of a resumable transfer, which is persisted as JSON with a MD5 of itself.
+pub(crate) trait Checkpoint: Serialize + DeserializeOwned {
+    fn md5_mut(&mut self) -> &mut String;
+}
+
+/// The size and modified time of a local file, used to detect changes between two runs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct FileStat {
+    pub(crate) size: u64,
+    pub(crate) mtime_secs: u64,
+    pub(crate) mtime_nanos: u32,
+}
+
+impl FileStat {
+    pub(crate) async fn of(path: &Path) -> Result<FileStat> {
+        let meta = tokio::fs::metadata(path).await?;
+        let mtime = meta.modified()?.duration_since(UNIX_EPOCH)?;
+        Ok(FileStat {
+            size: meta.len(),
+            mtime_secs: mtime.as_secs(),
+            mtime_nanos: mtime.subsec_nanos(),
+        })
+    }
+}
+
+/// Returns the checkpoint file in the dir, which is unique for the local file and the object.
+pub(crate) fn checkpoint_path(dir: &Path, file: &Path, bucket: &str, object: &str) -> PathBuf {
+    let file = match std::env::current_dir() {
+        Ok(cwd) if file.is_relative() => cwd.join(file),
+        _ => file.to_path_buf(),
+    };
+    let digest = md5::compute(format!("{}\noss://{}/{}", file.display(), bucket, object));
+    dir.join(format!("{:x}.cp", digest))
+}
+
+fn digest<T: Checkpoint>(cp: &mut T) -> Result<String> {
+    let origin = std::mem::take(cp.md5_mut());
+    let b = serde_json::to_vec(cp);
+    *cp.md5_mut() = origin;
+    Ok(format!("{:x}", md5::compute(b?)))
+}
+
+/// Loads a checkpoint, `None` will be returned if it doesn't exist or it is corrupted.
+pub(crate) async fn load<T: Checkpoint>(path: &Path) -> Result<Option<T>> {
+    let b = match tokio::fs::read(path).await {
+        Ok(b) => b,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut cp: T = match serde_json::from_slice(&b) {
+        Ok(it) => it,
+        Err(e) => {
+            warn!("ignore invalid checkpoint {}: {}", path.display(), e);
+            return Ok(None);
+        }
+    };
+
+    let expect = cp.md5_mut().clone();
+    if digest(&mut cp)? != expect {
+        warn!("ignore corrupted checkpoint {}", path.display());
+        return Ok(None);
+    }
+
+    Ok(Some(cp))
+}
+
+/// Saves a checkpoint atomically: write to a temporary file then rename it.
+pub(crate) async fn save<T: Checkpoint>(path: &Path, cp: &mut T) -> Result<()> {
+    *cp.md5_mut() = digest(cp)?;
+    let b = serde_json::to_vec(cp)?;
+
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    tokio::fs::write(&tmp, b).await?;
+    tokio::fs::rename(&tmp, path).await?;
+    Ok(())
+}
+
+pub(crate) async fn remove(path: &Path) -> Result<()> {
+    match tokio::fs::remove_file(path).await {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
@@ -1,7 +1,10 @@
 use std::sync::Arc;
+use std::time::Duration;
 
-use crate::config::ClientConfig;
+use crate::config::{AuthVersion, ClientConfig, HttpMaxConns, HttpProxy, HttpTimeout};
 use crate::conn::{Conn, UrlMaker};
+use crate::credentials::{CredentialsProvider, DefaultCredentialsChain};
+use crate::retry::RetryPolicy;
 use crate::util;
 use crate::{bucket::Bucket, Result};
 
@@ -20,10 +23,25 @@ impl Client {
     }
 
     fn new(config: ClientConfig) -> Result<Client> {
-        let client = reqwest::Client::builder()
+        let timeout = &config.http_timeout;
+        let max_conns = &config.http_max_conns;
+        let mut builder = reqwest::Client::builder()
             .http1_only()
-            .timeout(config.timeout)
-            .build()?;
+            .connect_timeout(timeout.connect)
+            .pool_idle_timeout(timeout.idle_conn)
+            .pool_max_idle_per_host(
+                max_conns
+                    .max_idle_conns_per_host
+                    .min(max_conns.max_idle_conns),
+            );
+        if let Some(it) = &config.http_proxy {
+            let mut proxy = reqwest::Proxy::all(&it.host)?;
+            if let Some(user) = &it.user {
+                proxy = proxy.basic_auth(user, it.password.as_deref().unwrap_or_default());
+            }
+            builder = builder.proxy(proxy);
+        }
+        let client = builder.build()?;
 
         let um = UrlMaker::new(&config.endpoint, config.cname, config.http_proxy.is_some())?;
         let config = Arc::new(config);
@@ -63,7 +81,176 @@ impl ClientBuilder {
         self
     }
 
-    pub fn build(self) -> Result<Client> {
+    /// Signs the requests with the credentials of the provider, which can be rotated.
+    /// It cannot be used together with the access key.
+    pub fn credentials_provider(mut self, provider: impl CredentialsProvider + 'static) -> Self {
+        self.config.credentials_provider = Some(Arc::new(provider));
+        self
+    }
+
+    /// The temporary security token of STS.
+    pub fn security_token(mut self, token: impl Into<String>) -> Self {
+        self.config.security_token = token.into();
+        self
+    }
+
+    /// Whether the endpoint is a custom domain bound to the bucket.
+    pub fn cname(mut self, cname: bool) -> Self {
+        self.config.cname = cname;
+        self
+    }
+
+    /// The version of the signature, defaults to `AuthVersion::V1`.
+    pub fn auth_version(mut self, version: AuthVersion) -> Self {
+        self.config.auth_version = version;
+        self
+    }
+
+    /// The region of the endpoint (e.g. `cn-hangzhou`), which is required by signature v4.
+    /// It is inferred from the endpoint if not set.
+    pub fn region(mut self, region: impl Into<String>) -> Self {
+        self.config.region = region.into();
+        self
+    }
+
+    /// The headers which are signed besides the default ones, only supported by signature
+    /// v2 and v4.
+    pub fn additional_headers<I, T>(mut self, headers: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<str>,
+    {
+        self.config.additional_headers = headers
+            .into_iter()
+            .map(|it| it.as_ref().to_lowercase())
+            .collect();
+        self
+    }
+
+    pub fn user_agent(mut self, ua: impl Into<String>) -> Self {
+        self.config.ua = ua.into();
+        self
+    }
+
+    /// The max number of retries of a failed request with exponential backoff, defaults to 3.
+    /// It is ignored if a retry policy is set. Only the idempotent requests are retried, the
+    /// append and the completion of a multipart upload have to opt in by their options.
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.config.retries = retries;
+        self
+    }
+
+    /// Decides whether and when a failed request is retried.
+    pub fn retry_policy(mut self, policy: impl RetryPolicy + 'static) -> Self {
+        self.config.retry_policy = Some(Arc::new(policy));
+        self
+    }
+
+    /// The timeout of a whole request, defaults to 60s. The requests which transfer the content
+    /// of an object use `HttpTimeout::long` instead, which is unlimited by default.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.config.timeout = timeout;
+        self
+    }
+
+    /// The timeouts of connecting, stalled reads and writes, waiting for the response headers,
+    /// transferring an object and keeping idle connections.
+    pub fn http_timeout(mut self, timeout: HttpTimeout) -> Self {
+        self.config.http_timeout = timeout;
+        self
+    }
+
+    /// The limits of the idle connections kept in the pool.
+    pub fn http_max_conns(mut self, max_conns: HttpMaxConns) -> Self {
+        self.config.http_max_conns = max_conns;
+        self
+    }
+
+    /// Sends the requests through a proxy, e.g. `http://127.0.0.1:8080`.
+    pub fn proxy(mut self, host: impl Into<String>) -> Self {
+        self.config.http_proxy = Some(HttpProxy {
+            host: host.into(),
+            user: None,
+            password: None,
+        });
+        self
+    }
+
+    /// Sends the requests through a proxy which requires basic authentication.
+    pub fn proxy_with_auth(
+        mut self,
+        host: impl Into<String>,
+        user: impl Into<String>,
+        password: impl Into<String>,
+    ) -> Self {
+        self.config.http_proxy = Some(HttpProxy {
+            host: host.into(),
+            user: Some(user.into()),
+            password: Some(password.into()),
+        });
+        self
+    }
+
+    /// Checks the crc64 of each put, append, upload_part and get request against the server,
+    /// defaults to false. Only these checks are opt-in, the crc64 of the whole file of
+    /// `upload_file` and `download_file` is always checked if the server returns it.
+    pub fn enable_crc(mut self, enable: bool) -> Self {
+        self.config.enable_crc = enable;
+        self
+    }
+
+    /// Sends a `Content-MD5` header with every upload, so that the server rejects corrupted
+    /// requests.
+    pub fn enable_md5(mut self, enable: bool) -> Self {
+        self.config.enable_md5 = enable;
+        self
+    }
+
+    /// The max size of a reader body buffered in memory when computing its MD5, a larger one is
+    /// spooled to a temp file. Defaults to 16MB.
+    pub fn md5_threshold(mut self, threshold: i64) -> Self {
+        self.config.md5_threshold = threshold;
+        self
+    }
+
+    /// Builds the client, the credentials are resolved by `DefaultCredentialsChain` if neither
+    /// the access key nor a provider is set.
+    pub fn build(mut self) -> Result<Client> {
+        self.config.validate()?;
+        if self.config.credentials_provider.is_none() && self.config.access_key_id.is_empty() {
+            self.config.credentials_provider = Some(Arc::new(DefaultCredentialsChain::new()));
+        }
         Client::new(self.config)
     }
 }
+
+#[cfg(test)]
+mod test_super {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_security_token() {
+        let cli = Client::builder()
+            .endpoint("https://oss-cn-hangzhou.aliyuncs.com")
+            .access_key("ak")
+            .access_secret("sk")
+            .security_token("token")
+            .build()
+            .unwrap();
+        let cred = cli.conn.credentials().await.unwrap();
+        assert_eq!(Some("token"), cred.security_token());
+
+        let url = cli
+            .bucket("bucket")
+            .unwrap()
+            .presign_url(
+                reqwest::Method::GET,
+                "a.txt",
+                Duration::from_secs(60),
+                Default::default(),
+            )
+            .await
+            .unwrap();
+        assert!(url.contains("security-token=token"));
+    }
+}
@@ -1,14 +1,21 @@
+use std::sync::Arc;
 use std::{fmt::Debug, time::Duration};
 
 use once_cell::sync::Lazy;
 
-use crate::types::Credentials;
-use crate::{util, VERSION};
+use crate::credentials::{Credentials, CredentialsProvider, StaticCredentials};
+use crate::retry::{ExponentialBackoff, RetryPolicy};
+use crate::{util, Result, VERSION};
 
+/// The version of the signature algorithm.
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub(crate) enum AuthVersion {
+pub enum AuthVersion {
+    /// HMAC-SHA1, which is deprecated.
     V1,
+    /// HMAC-SHA256.
     V2,
+    /// `OSS4-HMAC-SHA256`, the key is derived from the date, region and product.
+    V4,
 }
 
 impl Default for AuthVersion {
@@ -17,19 +24,51 @@ impl Default for AuthVersion {
     }
 }
 
-#[derive(Debug)]
-pub(crate) struct HttpTimeout {
-    pub(crate) connect: Duration,
-    pub(crate) read_write: Duration,
-    pub(crate) header: Duration,
-    pub(crate) long: Duration,
-    pub(crate) idle_conn: Duration,
+/// The timeouts of the http connections.
+#[derive(Debug, Clone)]
+pub struct HttpTimeout {
+    /// The timeout of establishing a connection.
+    pub connect: Duration,
+    /// The max time waiting for the next bytes when reading or writing a body.
+    pub read_write: Duration,
+    /// The max time waiting for the response headers.
+    pub header: Duration,
+    /// The timeout of a whole request which transfers the content of an object, it can be
+    /// overridden by the options of the request. There is no limit by default, a stalled
+    /// transfer is detected by `read_write` instead.
+    pub long: Option<Duration>,
+    /// How long an idle connection is kept in the pool.
+    pub idle_conn: Duration,
 }
 
-#[derive(Debug)]
-pub(crate) struct HttpMaxConns {
-    pub(crate) max_idle_conns: usize,
-    pub(crate) max_idle_conns_per_host: usize,
+impl Default for HttpTimeout {
+    fn default() -> Self {
+        HttpTimeout {
+            connect: Duration::from_secs(30),
+            read_write: Duration::from_secs(60),
+            header: Duration::from_secs(60),
+            long: None,
+            idle_conn: Duration::from_secs(50),
+        }
+    }
+}
+
+/// The limits of the connection pool.
+#[derive(Debug, Clone)]
+pub struct HttpMaxConns {
+    /// The max number of idle connections, the pool of reqwest is limited per host,
+    /// so it caps `max_idle_conns_per_host`.
+    pub max_idle_conns: usize,
+    pub max_idle_conns_per_host: usize,
+}
+
+impl Default for HttpMaxConns {
+    fn default() -> Self {
+        HttpMaxConns {
+            max_idle_conns: 100,
+            max_idle_conns_per_host: 100,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -45,13 +84,14 @@ pub(crate) struct ClientConfig {
     pub(crate) access_key_id: String,
     pub(crate) access_key_secret: String,
     pub(crate) retries: u32,
+    pub(crate) retry_policy: Option<Arc<dyn RetryPolicy>>,
     pub(crate) ua: String,
     pub(crate) debug: bool,
     pub(crate) timeout: Duration,
     pub(crate) security_token: String,
     pub(crate) cname: bool,
-    pub(crate) http_timeout: Option<HttpTimeout>,
-    pub(crate) http_max_conns: Option<HttpMaxConns>,
+    pub(crate) http_timeout: HttpTimeout,
+    pub(crate) http_max_conns: HttpMaxConns,
     pub(crate) http_proxy: Option<HttpProxy>,
     pub(crate) enable_md5: bool,
     pub(crate) md5_threshold: i64,
@@ -59,12 +99,17 @@ pub(crate) struct ClientConfig {
     pub(crate) enable_crc: bool,
     pub(crate) log_level: i8,
     pub(crate) upload_limit_speed: i64,
-    pub(crate) credentials_provider: Option<Box<dyn Credentials>>,
+    pub(crate) credentials_provider: Option<Arc<dyn CredentialsProvider>>,
     //...
     pub(crate) additional_headers: Vec<String>,
     pub(crate) auth_version: AuthVersion,
+    // required by signature v4, e.g. cn-hangzhou
+    pub(crate) region: String,
 }
 
+const DEFAULT_MD5_THRESHOLD: i64 = 16 * 1024 * 1024;
+const DEFAULT_RETRIES: u32 = 3;
+
 static DEFAULT_USER_AGENT: Lazy<String> = Lazy::new(|| {
     let os = util::SYS_INFO.name();
     let arch = util::SYS_INFO.machine();
@@ -87,7 +132,8 @@ impl Default for ClientConfig {
             endpoint: Default::default(),
             access_key_id: Default::default(),
             access_key_secret: Default::default(),
-            retries: Default::default(),
+            retries: DEFAULT_RETRIES,
+            retry_policy: Default::default(),
             ua,
             debug: Default::default(),
             timeout: Duration::from_secs(60),
@@ -97,36 +143,142 @@ impl Default for ClientConfig {
             http_max_conns: Default::default(),
             http_proxy: Default::default(),
             enable_md5: Default::default(),
-            md5_threshold: Default::default(),
+            md5_threshold: DEFAULT_MD5_THRESHOLD,
             enable_crc: Default::default(),
             log_level: Default::default(),
             upload_limit_speed: Default::default(),
             credentials_provider: Default::default(),
             additional_headers: Default::default(),
             auth_version: Default::default(),
+            region: Default::default(),
         }
     }
 }
 
-impl Credentials for ClientConfig {
-    fn access_key_id(&self) -> &str {
+impl ClientConfig {
+    /// Returns the provider of the credentials, the access key is used if it is not set.
+    pub(crate) fn credentials_provider(&self) -> Arc<dyn CredentialsProvider> {
         match &self.credentials_provider {
-            Some(it) => it.access_key_id(),
-            None => &self.access_key_id,
+            Some(it) => it.clone(),
+            None => {
+                let credentials = Credentials::new(&self.access_key_id, &self.access_key_secret)
+                    .with_security_token(&self.security_token);
+                Arc::new(StaticCredentials::from(credentials))
+            }
         }
     }
 
-    fn access_key_secret(&self) -> &str {
-        match &self.credentials_provider {
-            Some(it) => it.access_key_secret(),
-            None => &self.access_key_secret,
+    /// Returns the retry policy, an exponential backoff of `retries` if it is not set.
+    pub(crate) fn retry_policy(&self) -> Arc<dyn RetryPolicy> {
+        match &self.retry_policy {
+            Some(it) => it.clone(),
+            None => Arc::new(ExponentialBackoff::new(self.retries)),
         }
     }
 
-    fn security_token(&self) -> &str {
-        match &self.credentials_provider {
-            Some(it) => it.security_token(),
-            None => &self.security_token,
+    /// Checks the config before a client is built, the omitted region is inferred from the
+    /// endpoint.
+    pub(crate) fn validate(&mut self) -> Result<()> {
+        if self.endpoint.is_empty() {
+            bail!("endpoint is required!");
+        }
+        if self.access_key_id.is_empty() != self.access_key_secret.is_empty() {
+            bail!("access key id and access key secret should be set together!");
+        }
+        if self.credentials_provider.is_some() && !self.access_key_id.is_empty() {
+            bail!("access key and credentials provider cannot be set at the same time!");
+        }
+        if !self.security_token.is_empty() && self.access_key_id.is_empty() {
+            bail!("security token cannot be used without access key!");
         }
+        if self.ua.is_empty() {
+            bail!("user agent cannot be empty!");
+        }
+        if self.timeout.is_zero() {
+            bail!("timeout cannot be zero!");
+        }
+        if self.md5_threshold < 0 {
+            bail!("md5 threshold cannot be negative: {}", self.md5_threshold);
+        }
+        let it = &self.http_timeout;
+        if it.connect.is_zero()
+            || it.read_write.is_zero()
+            || it.header.is_zero()
+            || matches!(it.long, Some(it) if it.is_zero())
+        {
+            bail!("http timeouts cannot be zero: {:?}", it);
+        }
+        if let Some(it) = &self.http_proxy {
+            if it.host.is_empty() {
+                bail!("proxy host cannot be empty!");
+            }
+            if it.password.is_some() && it.user.is_none() {
+                bail!("proxy password cannot be used without user!");
+            }
+        }
+
+        match self.auth_version {
+            AuthVersion::V1 => {
+                if !self.additional_headers.is_empty() {
+                    bail!("additional headers are not supported by signature v1!");
+                }
+            }
+            AuthVersion::V2 => (),
+            AuthVersion::V4 => {
+                if self.region.is_empty() {
+                    self.region = match util::region_of_endpoint(&self.endpoint) {
+                        Some(it) => it,
+                        None => bail!(
+                            "region is required by signature v4, cannot infer it from endpoint '{}'",
+                            self.endpoint
+                        ),
+                    };
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test_super {
+    use super::*;
+
+    fn new_config() -> ClientConfig {
+        let mut config = ClientConfig::default();
+        config.endpoint = "https://oss-cn-hangzhou.aliyuncs.com".into();
+        config.access_key_id = "ak".into();
+        config.access_key_secret = "sk".into();
+        config
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(new_config().validate().is_ok());
+        assert!(ClientConfig::default().validate().is_err());
+
+        let mut config = new_config();
+        config.access_key_secret = "".into();
+        assert!(config.validate().is_err());
+
+        let mut config = new_config();
+        config.additional_headers = vec!["host".into()];
+        assert!(config.validate().is_err());
+        config.auth_version = AuthVersion::V2;
+        assert!(config.validate().is_ok());
+
+        let mut config = new_config();
+        config.auth_version = AuthVersion::V4;
+        assert!(config.validate().is_ok());
+        assert_eq!("cn-hangzhou", config.region);
+
+        let mut config = new_config();
+        config.endpoint = "http://127.0.0.1:8080".into();
+        config.auth_version = AuthVersion::V4;
+        assert!(config.validate().is_err());
+
+        let mut config = new_config();
+        config.http_timeout.long = Some(Duration::ZERO);
+        assert!(config.validate().is_err());
     }
 }
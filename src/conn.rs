@@ -2,24 +2,40 @@ use std::borrow::Cow;
 use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::fmt::Write;
 use std::net::Ipv4Addr;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
 use base64::{engine::general_purpose::STANDARD, Engine as _};
+use chrono::{DateTime, Utc};
 use hmac::{Hmac, Mac};
 use once_cell::sync::Lazy;
 use reqwest::Url;
 use sha1::Sha1;
-use sha2::Sha256;
+use sha2::{Digest, Sha256};
 
+use crate::body::Body;
 use crate::config::{AuthVersion, ClientConfig};
+use crate::credentials::{Credentials, CredentialsCache};
 use crate::error::{OSSError, ServiceError};
-use crate::types::{Credentials, Headers, Params, Request};
+use crate::retry::{RetryAttempts, RetryPolicy};
+use crate::timeout::{watch_stall, Progress};
+use crate::types::{
+    Headers, Params, Request, RequestOptions, HEADER_OSS_ERR, HEADER_OSS_HASH_CRC64,
+    HEADER_OSS_REQUEST_ID,
+};
 use crate::util;
 use crate::Result;
 
 type HmacSha1 = Hmac<Sha1>;
 type HmacSha256 = Hmac<Sha256>;
 
+const V4_ALGORITHM: &str = "OSS4-HMAC-SHA256";
+const V4_PRODUCT: &str = "oss";
+const V4_UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+// the max expiration of a presigned url of signature v4
+const V4_MAX_EXPIRES: u64 = 7 * 24 * 3600;
+
 static SIGN_KEYS: Lazy<HashSet<&'static str>> = Lazy::new(|| {
     let v = vec![
         "acl",
@@ -107,6 +123,8 @@ pub(crate) struct Conn {
     config: Arc<ClientConfig>,
     url_maker: Arc<UrlMaker>,
     client: reqwest::Client,
+    credentials: Arc<CredentialsCache>,
+    retry: Arc<dyn RetryPolicy>,
 }
 
 impl Conn {
@@ -115,13 +133,21 @@ impl Conn {
         url_maker: Arc<UrlMaker>,
         client: reqwest::Client,
     ) -> Conn {
+        let credentials = Arc::new(CredentialsCache::new(config.credentials_provider()));
+        let retry = config.retry_policy();
         Conn {
             config,
             url_maker,
             client,
+            credentials,
+            retry,
         }
     }
 
+    pub(crate) async fn credentials(&self) -> Result<Credentials> {
+        self.credentials.get().await
+    }
+
     pub(crate) async fn execute(
         &self,
         method: reqwest::Method,
@@ -129,9 +155,9 @@ impl Conn {
         object: &str,
         params: Option<Params>,
         headers: Option<Headers>,
-        data: Vec<u8>,
-        init_crc: u64,
-    ) -> Result<Vec<u8>> {
+        data: Body,
+        options: RequestOptions,
+    ) -> Result<reqwest::Response> {
         let url_params = match params {
             Some(ref it) => Some(Self::get_url_params(it)?),
             None => None,
@@ -154,24 +180,240 @@ impl Conn {
             headers: headers.unwrap_or_default(),
             params: params.unwrap_or_default(),
             body: data,
+            crc: None,
+            timeout: self.get_timeout(&options),
         };
 
-        self.do_request(req, resource, init_crc).await
+        self.do_request(req, resource, options).await
+    }
+
+    /// The timeout of the whole request, a transfer of the object content is not limited unless
+    /// `HttpTimeout::long` is set, since a stalled transfer is detected anyway.
+    fn get_timeout(&self, options: &RequestOptions) -> Option<Duration> {
+        match options.timeout {
+            Some(it) => Some(it),
+            None if options.transfer => self.config.http_timeout.long,
+            None => Some(self.config.timeout),
+        }
+    }
+
+    /// Signs the request into the query string of an URL, which is valid for `expires` since
+    /// `now`. The headers must be sent as is by the user of the URL.
+    pub(crate) async fn presign(
+        &self,
+        method: reqwest::Method,
+        bucket: &str,
+        object: &str,
+        mut params: Params,
+        mut headers: Headers,
+        now: DateTime<Utc>,
+        expires: Duration,
+    ) -> Result<String> {
+        let cred = self.credentials.get().await?;
+        let token = cred.security_token().unwrap_or_default();
+        let expiration = now.timestamp() + expires.as_secs() as i64;
+
+        match self.config.auth_version {
+            AuthVersion::V1 => {
+                // the date is replaced with the expiration time in the string to sign
+                headers.insert("date".into(), expiration.to_string());
+                if !token.is_empty() {
+                    params.insert("security-token".into(), Some(token.into()));
+                }
+            }
+            AuthVersion::V2 => {
+                headers.insert("date".into(), expiration.to_string());
+                if !token.is_empty() {
+                    params.insert("security-token".into(), Some(token.into()));
+                }
+                params.insert("x-oss-signature-version".into(), Some("OSS2".into()));
+                params.insert("x-oss-expires".into(), Some(expiration.to_string()));
+                params.insert(
+                    "x-oss-access-key-id".into(),
+                    Some(cred.access_key_id().into()),
+                );
+                let additional_list = self.get_additional_header_keys(&headers);
+                if !additional_list.is_empty() {
+                    let keys: Vec<_> = additional_list.into_iter().collect();
+                    params.insert("x-oss-additional-headers".into(), Some(keys.join(";")));
+                }
+            }
+            AuthVersion::V4 => {
+                if expires.as_secs() > V4_MAX_EXPIRES {
+                    bail!(
+                        "expires of signature v4 cannot be longer than {} seconds",
+                        V4_MAX_EXPIRES
+                    );
+                }
+                let date = format_v4_date(&now);
+                let credential = format!("{}/{}", cred.access_key_id(), self.get_v4_scope(&date));
+                if !token.is_empty() {
+                    params.insert("x-oss-security-token".into(), Some(token.into()));
+                }
+                params.insert("x-oss-signature-version".into(), Some(V4_ALGORITHM.into()));
+                params.insert("x-oss-credential".into(), Some(credential));
+                params.insert("x-oss-date".into(), Some(date));
+                params.insert("x-oss-expires".into(), Some(expires.as_secs().to_string()));
+                let additional_list = self.get_additional_header_keys(&headers);
+                if !additional_list.is_empty() {
+                    let keys: Vec<_> = additional_list.into_iter().collect();
+                    params.insert("x-oss-additional-headers".into(), Some(keys.join(";")));
+                }
+            }
+        }
+
+        let sub_resource = self.get_sub_resource(&params)?;
+        let resource = self.get_resource(bucket, object, &sub_resource)?;
+        let req = Request {
+            url: String::new(),
+            method,
+            headers,
+            params,
+            body: Body::empty(),
+            crc: None,
+            timeout: None,
+        };
+        let signstr = self.get_signed_str(&req, &resource, cred.access_key_secret());
+
+        let mut params = req.params;
+        match self.config.auth_version {
+            AuthVersion::V1 => {
+                params.insert("Expires".into(), Some(expiration.to_string()));
+                params.insert("OSSAccessKeyId".into(), Some(cred.access_key_id().into()));
+                params.insert("Signature".into(), Some(signstr));
+            }
+            AuthVersion::V2 | AuthVersion::V4 => {
+                params.insert("x-oss-signature".into(), Some(signstr));
+            }
+        }
+
+        let url_params = Self::get_url_params(&params)?;
+        Ok(self.url_maker.get_url(bucket, object, &url_params))
+    }
+
+    pub(crate) fn get_url(&self, bucket: &str, object: &str) -> String {
+        self.url_maker.get_url(bucket, object, "")
+    }
+
+    /// Returns the fields of the credential used by `PostObject`, which should be both in the
+    /// form and in the conditions of the policy.
+    pub(crate) fn post_credential_fields(
+        &self,
+        cred: &Credentials,
+        now: &DateTime<Utc>,
+    ) -> Result<Headers> {
+        let mut fields = Headers::new();
+        if let Some(token) = cred.security_token() {
+            fields.insert("x-oss-security-token".into(), token.into());
+        }
+        match self.config.auth_version {
+            AuthVersion::V1 => (),
+            AuthVersion::V2 => bail!("signature v2 is not supported by post policy!"),
+            AuthVersion::V4 => {
+                let date = format_v4_date(now);
+                let credential = format!("{}/{}", cred.access_key_id(), self.get_v4_scope(&date));
+                fields.insert("x-oss-signature-version".into(), V4_ALGORITHM.into());
+                fields.insert("x-oss-credential".into(), credential);
+                fields.insert("x-oss-date".into(), date);
+            }
+        }
+        Ok(fields)
+    }
+
+    /// Signs the base64 encoded policy of `PostObject`, returns the signature fields.
+    pub(crate) fn sign_post_policy(
+        &self,
+        cred: &Credentials,
+        policy: &str,
+        now: &DateTime<Utc>,
+    ) -> Result<Headers> {
+        let key_secret = cred.access_key_secret();
+        let mut fields = Headers::new();
+        match self.config.auth_version {
+            AuthVersion::V1 => {
+                let mut mac = HmacSha1::new_from_slice(key_secret.as_bytes())
+                    .expect("HMAC can take key of any size");
+                mac.update(policy.as_bytes());
+                fields.insert("OSSAccessKeyId".into(), cred.access_key_id().into());
+                fields.insert(
+                    "Signature".into(),
+                    STANDARD.encode(mac.finalize().into_bytes()),
+                );
+            }
+            AuthVersion::V2 => bail!("signature v2 is not supported by post policy!"),
+            AuthVersion::V4 => {
+                let key = self.get_v4_signing_key(key_secret, &format_v4_date(now));
+                fields.insert(
+                    "x-oss-signature".into(),
+                    hex_encode(&hmac_sha256(&key, policy)),
+                );
+            }
+        }
+        Ok(fields)
     }
 
     async fn do_request(
         &self,
         mut req: Request,
         resource: String,
-        init_crc: u64,
-    ) -> Result<Vec<u8>> {
-        self.handle_body(&mut req, init_crc);
+        options: RequestOptions,
+    ) -> Result<reqwest::Response> {
+        self.handle_body(&mut req, options.init_crc).await?;
+
+        // a non-idempotent request may have been applied by the server before the connection
+        // broke, e.g. an append, which fails if it is sent again
+        let max_retries = if options.idempotent || is_idempotent(&req.method) {
+            self.retry.max_retries()
+        } else {
+            0
+        };
+
+        let mut retries = 0;
+        loop {
+            // keep a copy to retry with, the crc64 of the copy is not touched by this attempt
+            let next = if retries < max_retries {
+                req.try_clone()
+            } else {
+                None
+            };
+            let err = match self.send_request(req, &resource).await {
+                Ok(resp) => return Ok(resp),
+                Err(e) => e,
+            };
+            req = match next {
+                Some(it) if self.retry.is_retryable(&err) => it,
+                _ if retries > 0 => {
+                    let attempts = RetryAttempts::new(retries + 1, &err);
+                    return Err(err.context(attempts));
+                }
+                _ => return Err(err),
+            };
+            retries += 1;
+            let delay = self.retry.backoff(retries);
+            warn!(
+                "retry {} {} in {:?} ({}/{}): {}",
+                req.method, req.url, delay, retries, max_retries, err
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// Signs and sends the request once, the error responses are converted to errors.
+    async fn send_request(&self, mut req: Request, resource: &str) -> Result<reqwest::Response> {
+        let client_crc = req.crc.clone();
 
         // TODO: http proxy
 
         // http time
-        let date = util::httptime();
-        req.headers.insert("date".into(), date);
+        let now = Utc::now();
+        req.headers
+            .insert("date".into(), util::format_httptime(&now));
+        if self.config.auth_version == AuthVersion::V4 {
+            req.headers
+                .insert("x-oss-date".into(), format_v4_date(&now));
+            req.headers
+                .insert("x-oss-content-sha256".into(), V4_UNSIGNED_PAYLOAD.into());
+        }
 
         // user-agent
         req.headers
@@ -184,43 +426,80 @@ impl Conn {
             }
         }
 
-        let token = self.config.security_token();
-        if !token.is_empty() {
+        // the credentials may be rotated, so they are fetched for each request
+        let cred = self.credentials.get().await?;
+        if let Some(token) = cred.security_token() {
             req.headers
                 .insert("x-oss-security-token".into(), token.into());
         }
 
-        self.sign_header(&mut req, resource);
+        self.sign_header(&mut req, resource.to_string(), &cred);
+
+        let timeout = &self.config.http_timeout;
+        let progress = Progress::new(!req.body.is_empty());
+        let send = req.send(&self.client, &progress);
+        let resp = watch_stall(send, &progress, timeout.read_write, timeout.header).await?;
+        if resp.status().is_success() {
+            if let Some(crc) = client_crc {
+                Self::check_crc(crc.load(Ordering::SeqCst), resp.headers())?;
+            }
+            return Ok(resp);
+        }
+
+        let status = resp.status();
+        if status == reqwest::StatusCode::NOT_MODIFIED {
+            return Err(OSSError::NotModified.into());
+        }
+
+        let request_id = resp
+            .headers()
+            .get(HEADER_OSS_REQUEST_ID)
+            .and_then(|it| it.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+
+        // responses without body (eg: HEAD) carry the error xml in the 'x-oss-err' header
+        let err_header = resp
+            .headers()
+            .get(HEADER_OSS_ERR)
+            .and_then(|it| STANDARD.decode(it.as_bytes()).ok());
 
-        let resp = req.send(&self.client).await?;
-        let status_code = resp.status().as_u16();
-        let is_success = resp.status().is_success();
-        let b = resp.bytes().await?.to_vec();
+        let mut b = resp.bytes().await?.to_vec();
+        if b.is_empty() {
+            if let Some(it) = err_header {
+                b = it;
+            }
+        }
+
+        if status == reqwest::StatusCode::PRECONDITION_FAILED {
+            return Err(OSSError::PreconditionFailed(request_id).into());
+        }
 
-        if is_success {
-            Ok(b)
-        } else if let Ok(e) = ServiceError::try_from_xml(&b) {
-            Err(OSSError::ServiceError(status_code, e.code, e.message, e.request_id).into())
+        if let Ok(e) = ServiceError::try_from_xml(&b) {
+            Err(OSSError::ServiceError(status.as_u16(), e.code, e.message, e.request_id).into())
+        } else if b.is_empty() {
+            let message = status.canonical_reason().unwrap_or_default().to_string();
+            Err(OSSError::ServiceError(status.as_u16(), String::new(), message, request_id).into())
         } else {
             bail!("{}", String::from_utf8_lossy(&b))
         }
     }
 
-    fn sign_header(&self, req: &mut Request, resource: String) {
+    fn sign_header(&self, req: &mut Request, resource: String, cred: &Credentials) {
         let additional_list = self.get_additional_header_keys(&req.headers);
 
-        let signstr = self.get_signed_str(req, &resource, self.config.access_key_secret());
+        let signstr = self.get_signed_str(req, &resource, cred.access_key_secret());
 
         let authorization_str = match self.config.auth_version {
             AuthVersion::V1 => {
-                format!("OSS {}:{}", self.config.access_key_id(), signstr)
+                format!("OSS {}:{}", cred.access_key_id(), signstr)
             }
             AuthVersion::V2 => {
                 let additional_list = self.get_additional_header_keys(&req.headers);
                 if additional_list.is_empty() {
                     format!(
                         "OSS2 AccessKeyId:{},Signature:{}",
-                        self.config.access_key_id(),
+                        cred.access_key_id(),
                         signstr
                     )
                 } else {
@@ -233,12 +512,35 @@ impl Conn {
                     }
                     format!(
                         "OSS2 AccessKeyId:{},AdditionalHeaders:{},Signature:{}",
-                        self.config.access_key_id(),
+                        cred.access_key_id(),
                         additionnal_headers_str,
                         signstr
                     )
                 }
             }
+            AuthVersion::V4 => {
+                let date = req
+                    .headers
+                    .get("x-oss-date")
+                    .map(|it| it.as_str())
+                    .unwrap_or_default();
+                let credential = format!("{}/{}", cred.access_key_id(), self.get_v4_scope(date));
+                if additional_list.is_empty() {
+                    format!(
+                        "{} Credential={},Signature={}",
+                        V4_ALGORITHM, credential, signstr
+                    )
+                } else {
+                    let keys: Vec<_> = additional_list.into_iter().collect();
+                    format!(
+                        "{} Credential={},AdditionalHeaders={},Signature={}",
+                        V4_ALGORITHM,
+                        credential,
+                        keys.join(";"),
+                        signstr
+                    )
+                }
+            }
         };
         req.headers
             .insert("authorization".into(), authorization_str);
@@ -256,6 +558,42 @@ impl Conn {
     }
 
     fn get_signed_str(&self, req: &Request, resource: &str, key_secret: &str) -> String {
+        match self.config.auth_version {
+            AuthVersion::V1 => {
+                let mut sign_str = self.get_string_to_sign(req);
+                sign_str.push_str(resource);
+                let mut mac = HmacSha1::new_from_slice(key_secret.as_bytes())
+                    .expect("HMAC can take key of any size");
+                mac.update(sign_str.as_bytes());
+                let res = mac.finalize();
+                let code = res.into_bytes();
+                STANDARD.encode(code)
+            }
+            AuthVersion::V2 => {
+                let mut sign_str = self.get_string_to_sign(req);
+                let additional_keys = self.get_additional_header_keys(&req.headers);
+                for (i, v) in additional_keys.iter().enumerate() {
+                    if i != 0 {
+                        sign_str.push(';');
+                    }
+                    sign_str.push_str(v);
+                }
+                sign_str.push('\n');
+                sign_str.push_str(resource);
+
+                let mut mac = HmacSha256::new_from_slice(key_secret.as_bytes())
+                    .expect("HMAC can take key of any size");
+                mac.update(sign_str.as_bytes());
+                let res = mac.finalize();
+                let code = res.into_bytes();
+                STANDARD.encode(code)
+            }
+            AuthVersion::V4 => self.get_signed_str_v4(req, resource, key_secret),
+        }
+    }
+
+    /// The string to sign of signature v1 and v2 without the canonicalized resource.
+    fn get_string_to_sign(&self, req: &Request) -> String {
         let mut hs = BTreeMap::new();
         let additional_keys = self.get_additional_header_keys(&req.headers);
 
@@ -299,54 +637,126 @@ impl Conn {
         sign_str.push('\n');
         sign_str.push_str(&canonicalized_oss_headers);
 
-        let sign = match self.config.auth_version {
-            AuthVersion::V1 => {
-                sign_str.push_str(resource);
-                let mut mac = HmacSha1::new_from_slice(key_secret.as_bytes())
-                    .expect("HMAC can take key of any size");
-                mac.update(sign_str.as_bytes());
-                let res = mac.finalize();
-                let code = res.into_bytes();
-                STANDARD.encode(code)
-            }
-            AuthVersion::V2 => {
-                for (i, v) in additional_keys.iter().enumerate() {
-                    if i != 0 {
-                        sign_str.push(';');
-                    }
-                    sign_str.push_str(v);
-                }
-                sign_str.push('\n');
-                sign_str.push_str(resource);
+        sign_str
+    }
 
-                let mut mac = HmacSha256::new_from_slice(key_secret.as_bytes())
-                    .expect("HMAC can take key of any size");
-                mac.update(sign_str.as_bytes());
-                let res = mac.finalize();
-                let code = res.into_bytes();
-                STANDARD.encode(code)
+    // the resource of signature v4 is the canonical uri and query string joined by '?'
+    fn get_signed_str_v4(&self, req: &Request, resource: &str, key_secret: &str) -> String {
+        let (canonical_uri, canonical_query) = resource.split_once('?').unwrap_or((resource, ""));
+        let additional_keys = self.get_additional_header_keys(&req.headers);
+
+        let mut hs = BTreeMap::new();
+        for (k, v) in &req.headers {
+            let k = k.to_lowercase();
+            if k == "content-type"
+                || k == "content-md5"
+                || k.starts_with("x-oss-")
+                || additional_keys.contains(&k)
+            {
+                hs.insert(k, v.trim());
             }
-        };
+        }
+        let mut canonical_headers = String::new();
+        for (k, v) in &hs {
+            canonical_headers.write_str(k).ok();
+            canonical_headers.write_char(':').ok();
+            canonical_headers.write_str(v).ok();
+            canonical_headers.write_char('\n').ok();
+        }
 
-        // debug!("sign: before={}, after={}", sign_str, sign);
+        let additional_headers: Vec<_> = additional_keys.into_iter().collect();
+        let payload = req
+            .headers
+            .get("x-oss-content-sha256")
+            .map(|it| it.as_str())
+            .unwrap_or(V4_UNSIGNED_PAYLOAD);
 
-        sign
+        let canonical_request = format!(
+            "{}\n{}\n{}\n{}\n{}\n{}",
+            req.method.as_str(),
+            canonical_uri,
+            canonical_query,
+            canonical_headers,
+            additional_headers.join(";"),
+            payload
+        );
+
+        // the timestamp is in the header, or in the query string of a presigned url
+        let timestamp = req
+            .headers
+            .get("x-oss-date")
+            .or_else(|| req.params.get("x-oss-date").and_then(|it| it.as_ref()))
+            .map(|it| it.as_str())
+            .unwrap_or_default();
+        let scope = self.get_v4_scope(timestamp);
+        let sign_str = format!(
+            "{}\n{}\n{}\n{}",
+            V4_ALGORITHM,
+            timestamp,
+            scope,
+            hex_encode(&Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let key = self.get_v4_signing_key(key_secret, timestamp);
+        hex_encode(&hmac_sha256(&key, &sign_str))
     }
 
-    fn handle_body(&self, req: &mut Request, init_crc: u64) {
-        let length = req.body.len();
-        req.headers
-            .insert("content-length".into(), length.to_string());
+    /// Derives the signing key of signature v4 from the date, region and product.
+    fn get_v4_signing_key(&self, key_secret: &str, date: &str) -> Vec<u8> {
+        let date = date.get(..8).unwrap_or_default();
+        let key = hmac_sha256(format!("aliyun_v4{}", key_secret).as_bytes(), date);
+        let key = hmac_sha256(&key, &self.config.region);
+        let key = hmac_sha256(&key, V4_PRODUCT);
+        hmac_sha256(&key, "aliyun_v4_request")
+    }
+
+    /// Returns the credential scope of signature v4, the date can be a full timestamp.
+    fn get_v4_scope(&self, date: &str) -> String {
+        format!(
+            "{}/{}/{}/aliyun_v4_request",
+            date.get(..8).unwrap_or_default(),
+            self.config.region,
+            V4_PRODUCT
+        )
+    }
+
+    fn check_crc(client_crc: u64, headers: &reqwest::header::HeaderMap) -> Result<()> {
+        let server_crc = headers
+            .get(HEADER_OSS_HASH_CRC64)
+            .and_then(|it| it.to_str().ok())
+            .and_then(|it| it.parse::<u64>().ok());
+        match server_crc {
+            Some(it) if it != client_crc => Err(OSSError::CrcMismatch(client_crc, it).into()),
+            _ => Ok(()),
+        }
+    }
 
-        if !req.body.is_empty() && self.config.enable_md5 {
-            // TODO: md5 threshold
-            let md5sum = format!("{:x}", md5::compute(&req.body));
+    async fn handle_body(&self, req: &mut Request, init_crc: Option<u64>) -> Result<()> {
+        // computing the md5 may spool a reader to a temp file, which makes its length known
+        if !req.body.is_empty()
+            && self.config.enable_md5
+            && !req.headers.contains_key("content-md5")
+        {
+            let threshold = self.config.md5_threshold.max(0) as u64;
+            let md5sum = req.body.content_md5(threshold).await?;
             req.headers.insert("content-md5".into(), md5sum);
         }
 
-        if !req.body.is_empty() && self.config.enable_crc {
-            // TODO: crc
+        // an unknown length means the body will be sent with chunked encoding
+        if let Some(length) = req.body.len()? {
+            req.headers
+                .insert("content-length".into(), length.to_string());
         }
+
+        // the crc64 will be compared with the one returned by server, which is the crc64 of the
+        // whole object, so the crc of an appendable object should be continued from 'init_crc'
+        if self.config.enable_crc {
+            if let Some(init_crc) = init_crc {
+                req.crc = Some(Arc::new(AtomicU64::new(init_crc)));
+            }
+        }
+
+        Ok(())
     }
 
     fn get_resource(&self, bucket: &str, object: &str, sub_resource: &str) -> Result<String> {
@@ -363,6 +773,7 @@ impl Conn {
                     // %2F ==> '/'
                     Ok(format!("%2F{}", sub_resource))
                 }
+                AuthVersion::V4 => Ok(format!("/{}", sub_resource)),
             }
         } else {
             match self.config.auth_version {
@@ -375,16 +786,56 @@ impl Conn {
                     sb.write_str(&sub_resource).ok();
                     Ok(sb)
                 }
+                AuthVersion::V4 => Ok(format!(
+                    "/{}/{}{}",
+                    bucket,
+                    util::uri_encode(object, true),
+                    sub_resource
+                )),
             }
         }
     }
 
     fn get_url_params(params: &Params) -> Result<String> {
-        let s = serde_urlencoded::to_string(params)?;
-        Ok(s.replace('+', "%20"))
+        // sub-resources without value (eg: '?uploads') must be kept as a bare key
+        let mut buf = String::new();
+        for (i, (k, v)) in params.iter().enumerate() {
+            if i != 0 {
+                buf.write_char('&').ok();
+            }
+            buf.write_str(&util::query_escape(k)).ok();
+            if let Some(it) = v {
+                buf.write_char('=').ok();
+                buf.write_str(&util::query_escape(it)).ok();
+            }
+        }
+        Ok(buf)
     }
 
     fn get_sub_resource(&self, params: &Params) -> Result<String> {
+        if self.config.auth_version == AuthVersion::V4 {
+            // all the params are signed, sorted by the encoded keys
+            let mut sign_params: Vec<_> = params
+                .iter()
+                .map(|(k, v)| {
+                    let v = v
+                        .as_ref()
+                        .filter(|it| !it.is_empty())
+                        .map(|it| util::uri_encode(it, false));
+                    (util::uri_encode(k, false), v)
+                })
+                .collect();
+            sign_params.sort();
+            let sign_params: Vec<_> = sign_params
+                .into_iter()
+                .map(|(k, v)| match v {
+                    Some(v) => format!("{}={}", k, v),
+                    None => k,
+                })
+                .collect();
+            return Ok(sign_params.join("&"));
+        }
+
         let mut sign_params = BTreeMap::<String, Option<String>>::new();
         for (k, v) in params {
             if self.config.auth_version == AuthVersion::V2 {
@@ -422,6 +873,36 @@ impl Conn {
     }
 }
 
+/// Returns true if sending the request more than once has the same effect as sending it once.
+fn is_idempotent(method: &reqwest::Method) -> bool {
+    matches!(
+        *method,
+        reqwest::Method::GET
+            | reqwest::Method::HEAD
+            | reqwest::Method::PUT
+            | reqwest::Method::DELETE
+    )
+}
+
+fn hmac_sha256(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC can take key of any size");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex_encode(b: &[u8]) -> String {
+    let mut s = String::with_capacity(b.len() * 2);
+    for it in b {
+        write!(s, "{:02x}", it).ok();
+    }
+    s
+}
+
+/// Formats the time as ISO 8601 basic format, e.g. `20231203T121212Z`.
+fn format_v4_date(t: &DateTime<Utc>) -> String {
+    t.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
 #[derive(Debug, Clone)]
 pub(crate) enum UrlType {
     CNAME,
@@ -459,10 +940,14 @@ impl UrlMaker {
                         }
                     };
 
+                    let net_loc = match url.port() {
+                        Some(port) => format!("{}:{}", host, port),
+                        None => host.into(),
+                    };
                     Ok(UrlMaker {
                         is_proxy,
                         schema: schema.into(),
-                        net_loc: host.into(),
+                        net_loc,
                         typ,
                     })
                 }
@@ -482,7 +967,7 @@ impl UrlMaker {
     }
 
     // build to (host,path)
-    fn build_url(&self, bucket: &str, object: &str) -> (Cow<str>, Cow<str>) {
+    fn build_url(&self, bucket: &str, object: &str) -> (Cow<'_, str>, Cow<'_, str>) {
         let object = util::query_escape(object);
         match self.typ {
             UrlType::CNAME => {
@@ -513,3 +998,372 @@ impl UrlMaker {
         }
     }
 }
+
+#[cfg(test)]
+mod test_super {
+    use super::*;
+
+    #[test]
+    fn test_get_url_params() {
+        let mut params = Params::new();
+        params.insert("objectMeta".into(), None);
+        assert_eq!("objectMeta", Conn::get_url_params(&params).unwrap());
+
+        params.insert("prefix".into(), Some("a b/c".into()));
+        assert_eq!(
+            "objectMeta&prefix=a%20b%2Fc",
+            Conn::get_url_params(&params).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_get_timeout() {
+        let conn = new_v4_conn();
+        let timeout = Duration::from_secs(1);
+        let options = RequestOptions::default();
+        assert_eq!(Some(conn.config.timeout), conn.get_timeout(&options));
+        let options = RequestOptions {
+            transfer: true,
+            ..Default::default()
+        };
+        assert_eq!(None, conn.get_timeout(&options));
+        let options = RequestOptions {
+            timeout: Some(timeout),
+            transfer: true,
+            ..Default::default()
+        };
+        assert_eq!(Some(timeout), conn.get_timeout(&options));
+
+        let mut config = ClientConfig::default();
+        config.http_timeout.long = Some(timeout);
+        let um = UrlMaker::new("https://oss-cn-hangzhou.aliyuncs.com", false, false).unwrap();
+        let conn = Conn::new(Arc::new(config), Arc::new(um), reqwest::Client::new());
+        let options = RequestOptions {
+            transfer: true,
+            ..Default::default()
+        };
+        assert_eq!(Some(timeout), conn.get_timeout(&options));
+    }
+
+    #[test]
+    fn test_url_maker() {
+        let um = UrlMaker::new("https://oss-cn-hangzhou.aliyuncs.com", false, false).unwrap();
+        assert_eq!(
+            "https://bucket.oss-cn-hangzhou.aliyuncs.com/a.txt",
+            um.get_url("bucket", "a.txt", "")
+        );
+
+        // the port of the endpoint is kept
+        let um = UrlMaker::new("http://127.0.0.1:8080", false, false).unwrap();
+        assert_eq!(
+            "http://127.0.0.1:8080/bucket/a.txt",
+            um.get_url("bucket", "a.txt", "")
+        );
+        let um = UrlMaker::new("http://oss.example.com:8080", true, false).unwrap();
+        assert_eq!(
+            "http://oss.example.com:8080/a.txt",
+            um.get_url("bucket", "a.txt", "")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_presign() {
+        let mut config = ClientConfig::default();
+        config.access_key_id = "ak".into();
+        config.access_key_secret = "sk".into();
+        config.security_token = "token".into();
+        let um = UrlMaker::new("https://oss-cn-hangzhou.aliyuncs.com", false, false).unwrap();
+        let conn = Conn::new(Arc::new(config), Arc::new(um), reqwest::Client::new());
+
+        let mut params = Params::new();
+        params.insert("x-oss-process".into(), Some("image/resize,w_100".into()));
+        let now = DateTime::from_timestamp(1699999940, 0).unwrap();
+        let url = conn
+            .presign(
+                reqwest::Method::GET,
+                "bucket",
+                "a.jpg",
+                params,
+                Headers::new(),
+                now,
+                Duration::from_secs(60),
+            )
+            .await
+            .unwrap();
+
+        let sign_str = "GET\n\n\n1700000000\n/bucket/a.jpg?security-token=token&x-oss-process=image/resize,w_100";
+        let mut mac = HmacSha1::new_from_slice(b"sk").unwrap();
+        mac.update(sign_str.as_bytes());
+        let signature = util::query_escape(&STANDARD.encode(mac.finalize().into_bytes()));
+
+        let url = Url::parse(&url).unwrap();
+        assert_eq!(Some("bucket.oss-cn-hangzhou.aliyuncs.com"), url.host_str());
+        assert_eq!("/a.jpg", url.path());
+        let query = url.query().unwrap();
+        assert!(query.contains("Expires=1700000000"));
+        assert!(query.contains("OSSAccessKeyId=ak"));
+        assert!(query.contains("security-token=token"));
+        assert!(query.contains(&format!("Signature={}", signature)));
+    }
+
+    fn new_v4_conn() -> Conn {
+        let mut config = ClientConfig::default();
+        config.access_key_id = "ak".into();
+        config.access_key_secret = "sk".into();
+        config.auth_version = AuthVersion::V4;
+        config.region = "cn-hangzhou".into();
+        let um = UrlMaker::new("https://oss-cn-hangzhou.aliyuncs.com", false, false).unwrap();
+        Conn::new(Arc::new(config), Arc::new(um), reqwest::Client::new())
+    }
+
+    #[test]
+    fn test_v4_canonical_resource() {
+        let conn = new_v4_conn();
+        let mut params = Params::new();
+        params.insert("uploads".into(), None);
+        params.insert("prefix".into(), Some("a b/c".into()));
+        params.insert("acl".into(), Some("".into()));
+        let sub_resource = conn.get_sub_resource(&params).unwrap();
+        assert_eq!("acl&prefix=a%20b%2Fc&uploads", sub_resource);
+        assert_eq!(
+            "/bucket/dir/a%20b.txt?acl&prefix=a%20b%2Fc&uploads",
+            conn.get_resource("bucket", "dir/a b.txt", &sub_resource)
+                .unwrap()
+        );
+        assert_eq!("/", conn.get_resource("", "", "").unwrap());
+        assert_eq!(
+            "20231203/cn-hangzhou/oss/aliyun_v4_request",
+            conn.get_v4_scope("20231203T121212Z")
+        );
+    }
+
+    // the example of the signature v4 in the OSS documentation
+    #[test]
+    fn test_v4_sign_header() {
+        let conn = new_v4_conn();
+        let mut headers = Headers::new();
+        headers.insert("x-oss-head1".into(), "value".into());
+        headers.insert("abc".into(), "value".into());
+        headers.insert("ZAbc".into(), "value".into());
+        headers.insert("XYZ".into(), "value".into());
+        headers.insert("content-type".into(), "text/plain".into());
+        headers.insert("x-oss-content-sha256".into(), V4_UNSIGNED_PAYLOAD.into());
+        headers.insert("x-oss-date".into(), "20231216T162057Z".into());
+        let mut params = Params::new();
+        params.insert("param1".into(), Some("value1".into()));
+        params.insert("+param1".into(), Some("value3".into()));
+        params.insert("|param1".into(), Some("value4".into()));
+        params.insert("+param2".into(), None);
+        params.insert("|param2".into(), None);
+        params.insert("param2".into(), None);
+
+        let sub_resource = conn.get_sub_resource(&params).unwrap();
+        let resource = conn
+            .get_resource("bucket", "1234+-/123/1.txt", &sub_resource)
+            .unwrap();
+        let mut req = Request {
+            url: "".into(),
+            method: reqwest::Method::PUT,
+            headers,
+            params,
+            body: Body::empty(),
+            crc: None,
+            timeout: None,
+        };
+        conn.sign_header(&mut req, resource, &Credentials::new("ak", "sk"));
+        assert_eq!(
+            "OSS4-HMAC-SHA256 Credential=ak/20231216/cn-hangzhou/oss/aliyun_v4_request,\
+             Signature=e21d18daa82167720f9b1047ae7e7f1ce7cb77a31e8203a7d5f4624fa0284afe",
+            req.headers["authorization"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_v4_presign() {
+        let conn = new_v4_conn();
+        let now = DateTime::from_timestamp(1701605532, 0).unwrap();
+        let url = conn
+            .presign(
+                reqwest::Method::GET,
+                "bucket",
+                "a.txt",
+                Params::new(),
+                Headers::new(),
+                now,
+                Duration::from_secs(60),
+            )
+            .await
+            .unwrap();
+        let url = Url::parse(&url).unwrap();
+        let query: BTreeMap<_, _> = url.query_pairs().into_owned().collect();
+        assert_eq!(V4_ALGORITHM, query["x-oss-signature-version"]);
+        assert_eq!(
+            "ak/20231203/cn-hangzhou/oss/aliyun_v4_request",
+            query["x-oss-credential"]
+        );
+        assert_eq!("20231203T121212Z", query["x-oss-date"]);
+        assert_eq!("60", query["x-oss-expires"]);
+        assert_eq!(64, query["x-oss-signature"].len());
+
+        // the example of the presigned url of signature v4 in the OSS documentation
+        let mut headers = Headers::new();
+        headers.insert("x-oss-head1".into(), "value".into());
+        headers.insert("abc".into(), "value".into());
+        headers.insert("ZAbc".into(), "value".into());
+        headers.insert("XYZ".into(), "value".into());
+        headers.insert("content-type".into(), "application/octet-stream".into());
+        let mut params = Params::new();
+        params.insert("param1".into(), Some("value1".into()));
+        params.insert("+param1".into(), Some("value3".into()));
+        params.insert("|param1".into(), Some("value4".into()));
+        params.insert("+param2".into(), None);
+        params.insert("|param2".into(), None);
+        params.insert("param2".into(), None);
+        let url = conn
+            .presign(
+                reqwest::Method::PUT,
+                "bucket",
+                "1234+-/123/1.txt",
+                params,
+                headers,
+                DateTime::from_timestamp(1702781677, 0).unwrap(),
+                Duration::from_secs(599),
+            )
+            .await
+            .unwrap();
+        let url = Url::parse(&url).unwrap();
+        let query: BTreeMap<_, _> = url.query_pairs().into_owned().collect();
+        assert_eq!(
+            "ak/20231217/cn-hangzhou/oss/aliyun_v4_request",
+            query["x-oss-credential"]
+        );
+        assert_eq!("599", query["x-oss-expires"]);
+        assert_eq!(
+            "a39966c61718be0d5b14e668088b3fa07601033f6518ac7b523100014269c0fe",
+            query["x-oss-signature"]
+        );
+        assert!(!query.contains_key("x-oss-additional-headers"));
+
+        let res = conn
+            .presign(
+                reqwest::Method::GET,
+                "bucket",
+                "a.txt",
+                Params::new(),
+                Headers::new(),
+                now,
+                Duration::from_secs(V4_MAX_EXPIRES + 1),
+            )
+            .await;
+        assert!(res.is_err());
+    }
+
+    fn new_retry_conn(endpoint: &str) -> Conn {
+        let mut config = ClientConfig::default();
+        config.access_key_id = "ak".into();
+        config.access_key_secret = "sk".into();
+        config.retry_policy = Some(Arc::new(
+            crate::retry::ExponentialBackoff::new(2).base_delay(Duration::from_millis(1)),
+        ));
+        let um = UrlMaker::new(endpoint, false, false).unwrap();
+        Conn::new(Arc::new(config), Arc::new(um), reqwest::Client::new())
+    }
+
+    #[tokio::test]
+    async fn test_retry() {
+        let count = Arc::new(AtomicU64::new(0));
+        let counter = count.clone();
+        let (endpoint, requests) =
+            crate::test_support::serve(move |_| match counter.fetch_add(1, Ordering::SeqCst) {
+                0 => (503, "".into()),
+                _ => (200, "".into()),
+            })
+            .await;
+
+        let conn = new_retry_conn(&endpoint);
+        let body = Body::from("hello");
+        let resp = conn
+            .execute(
+                reqwest::Method::PUT,
+                "bucket",
+                "a.txt",
+                None,
+                None,
+                body,
+                Default::default(),
+            )
+            .await
+            .unwrap();
+        assert!(resp.status().is_success());
+        for req in requests.lock().unwrap().iter() {
+            assert_eq!("hello", req.body);
+        }
+        assert_eq!(2, requests.lock().unwrap().len());
+
+        // a reader cannot be replayed
+        let body = Body::from_reader(&b"hello"[..]);
+        count.store(0, Ordering::SeqCst);
+        let e = conn
+            .execute(
+                reqwest::Method::PUT,
+                "bucket",
+                "a.txt",
+                None,
+                None,
+                body,
+                Default::default(),
+            )
+            .await
+            .unwrap_err();
+        assert!(e.downcast_ref::<RetryAttempts>().is_none());
+        assert_eq!(3, requests.lock().unwrap().len());
+    }
+
+    #[tokio::test]
+    async fn test_retry_exhausted() {
+        let (endpoint, requests) = crate::test_support::serve(|_| {
+            let xml = "<Error><Code>InternalError</Code><Message>oops</Message></Error>";
+            (500, xml.into())
+        })
+        .await;
+
+        let conn = new_retry_conn(&endpoint);
+        let e = conn
+            .execute(
+                reqwest::Method::GET,
+                "",
+                "a.txt",
+                None,
+                None,
+                Body::empty(),
+                Default::default(),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(3, e.downcast_ref::<RetryAttempts>().unwrap().attempts());
+        assert!(matches!(
+            e.downcast_ref::<OSSError>(),
+            Some(OSSError::ServiceError(500, ..))
+        ));
+        assert!(e.to_string().contains("InternalError"));
+        assert_eq!(3, requests.lock().unwrap().len());
+
+        // the errors which are not transient are not retried
+        let (endpoint, requests) = crate::test_support::serve(|_| (404, "".into())).await;
+        let conn = new_retry_conn(&endpoint);
+        let e = conn
+            .execute(
+                reqwest::Method::GET,
+                "",
+                "a.txt",
+                None,
+                None,
+                Body::empty(),
+                Default::default(),
+            )
+            .await
+            .unwrap_err();
+        assert!(e.downcast_ref::<RetryAttempts>().is_none());
+        assert_eq!(1, requests.lock().unwrap().len());
+    }
+}
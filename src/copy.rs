@@ -0,0 +1,475 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::io::Cursor;
+
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use reqwest::header::HeaderMap;
+use serde::Deserialize;
+
+use crate::body::Body;
+use crate::bucket::Bucket;
+use crate::multipart::{CompleteMultipartUploadResult, PartInfo, MAX_PART_NUMBER};
+use crate::object::{header_str, PutObjectOptions};
+use crate::types::{
+    Headers, Params, HEADER_OSS_HASH_CRC64, HEADER_OSS_REQUEST_ID, HEADER_OSS_VERSION_ID,
+};
+use crate::util;
+use crate::Result;
+
+/// The max size of an object which can be copied by a single `CopyObject` request.
+pub(crate) const MAX_COPY_OBJECT_SIZE: u64 = 1024 * 1024 * 1024;
+
+const HEADER_OSS_COPY_SOURCE_VERSION_ID: &str = "x-oss-copy-source-version-id";
+
+const MIN_PART_SIZE: u64 = 100 * 1024;
+const DEFAULT_PART_SIZE: u64 = 64 * 1024 * 1024;
+const DEFAULT_PARALLEL: usize = 3;
+
+/// How the metadata or the tags of the target object are set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyDirective {
+    /// Copies from the source object.
+    Copy,
+    /// Replaces with the ones in the request.
+    Replace,
+}
+
+impl Display for CopyDirective {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CopyDirective::Copy => write!(f, "COPY"),
+            CopyDirective::Replace => write!(f, "REPLACE"),
+        }
+    }
+}
+
+/// Options of `Bucket::copy_object_with_options`.
+///
+/// A conditional copy which is not satisfied fails with `OSSError::NotModified`
+/// or `OSSError::PreconditionFailed`.
+#[derive(Debug, Clone)]
+pub struct CopyObjectOptions {
+    source_bucket: Option<String>,
+    source_version_id: Option<String>,
+    metadata_directive: CopyDirective,
+    tagging_directive: CopyDirective,
+    object: PutObjectOptions,
+    if_match: Option<String>,
+    if_none_match: Option<String>,
+    if_modified_since: Option<DateTime<Utc>>,
+    if_unmodified_since: Option<DateTime<Utc>>,
+    part_size: u64,
+    parallel: usize,
+}
+
+impl Default for CopyObjectOptions {
+    fn default() -> Self {
+        CopyObjectOptions {
+            source_bucket: None,
+            source_version_id: None,
+            metadata_directive: CopyDirective::Copy,
+            tagging_directive: CopyDirective::Copy,
+            object: Default::default(),
+            if_match: None,
+            if_none_match: None,
+            if_modified_since: None,
+            if_unmodified_since: None,
+            part_size: DEFAULT_PART_SIZE,
+            parallel: DEFAULT_PARALLEL,
+        }
+    }
+}
+
+impl CopyObjectOptions {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Copies from another bucket in the same region, defaults to the target bucket.
+    pub fn source_bucket(mut self, bucket: impl Into<String>) -> Self {
+        self.source_bucket = Some(bucket.into());
+        self
+    }
+
+    /// Copies a specific version of the source object.
+    pub fn source_version_id(mut self, version_id: impl Into<String>) -> Self {
+        self.source_version_id = Some(version_id.into());
+        self
+    }
+
+    /// Whether the content type and user metadata are copied from the source object,
+    /// or replaced with the object options. Defaults to `CopyDirective::Copy`.
+    pub fn metadata_directive(mut self, directive: CopyDirective) -> Self {
+        self.metadata_directive = directive;
+        self
+    }
+
+    /// Whether the tags are copied from the source object, or replaced with the tags of the
+    /// object options. Defaults to `CopyDirective::Copy`.
+    pub fn tagging_directive(mut self, directive: CopyDirective) -> Self {
+        self.tagging_directive = directive;
+        self
+    }
+
+    /// Sets the storage class, ACL... of the target object. The content type, user metadata
+    /// and tags only take effect with `CopyDirective::Replace`.
+    pub fn object_options(mut self, options: PutObjectOptions) -> Self {
+        self.object = options;
+        self
+    }
+
+    pub fn if_match(mut self, etag: impl Into<String>) -> Self {
+        self.if_match = Some(etag.into());
+        self
+    }
+
+    pub fn if_none_match(mut self, etag: impl Into<String>) -> Self {
+        self.if_none_match = Some(etag.into());
+        self
+    }
+
+    pub fn if_modified_since(mut self, t: DateTime<Utc>) -> Self {
+        self.if_modified_since = Some(t);
+        self
+    }
+
+    pub fn if_unmodified_since(mut self, t: DateTime<Utc>) -> Self {
+        self.if_unmodified_since = Some(t);
+        self
+    }
+
+    /// The size of each part when the source is larger than 1GB, at least 100KB.
+    pub fn part_size(mut self, part_size: u64) -> Self {
+        self.part_size = part_size.max(MIN_PART_SIZE);
+        self
+    }
+
+    /// The number of parts copied at the same time when the source is larger than 1GB.
+    pub fn parallel(mut self, parallel: usize) -> Self {
+        self.parallel = parallel.max(1);
+        self
+    }
+
+    pub(crate) fn source_bucket_name<'a>(&'a self, bucket: &'a str) -> &'a str {
+        self.source_bucket.as_deref().unwrap_or(bucket)
+    }
+
+    pub(crate) fn source_version_params(&self) -> Option<Params> {
+        self.source_version_id.as_ref().map(|it| {
+            let mut params = Params::new();
+            params.insert("versionId".into(), Some(it.clone()));
+            params
+        })
+    }
+
+    /// Returns the headers which specify the source object, shared by `CopyObject`
+    /// and `UploadPartCopy`.
+    pub(crate) fn source_headers(&self, bucket: &str, object: &str) -> Headers {
+        let mut source = format!(
+            "/{}/{}",
+            self.source_bucket_name(bucket),
+            util::query_escape(object)
+        );
+        if let Some(it) = &self.source_version_id {
+            source.push_str("?versionId=");
+            source.push_str(it);
+        }
+
+        let mut headers = Headers::new();
+        headers.insert("x-oss-copy-source".into(), source);
+        if let Some(it) = &self.if_match {
+            headers.insert("x-oss-copy-source-if-match".into(), it.clone());
+        }
+        if let Some(it) = &self.if_none_match {
+            headers.insert("x-oss-copy-source-if-none-match".into(), it.clone());
+        }
+        if let Some(it) = &self.if_modified_since {
+            headers.insert(
+                "x-oss-copy-source-if-modified-since".into(),
+                util::format_httptime(it),
+            );
+        }
+        if let Some(it) = &self.if_unmodified_since {
+            headers.insert(
+                "x-oss-copy-source-if-unmodified-since".into(),
+                util::format_httptime(it),
+            );
+        }
+        headers
+    }
+
+    pub(crate) fn into_headers(self, bucket: &str, object: &str) -> Headers {
+        let mut headers = self.source_headers(bucket, object);
+        headers.insert(
+            "x-oss-metadata-directive".into(),
+            self.metadata_directive.to_string(),
+        );
+        headers.insert(
+            "x-oss-tagging-directive".into(),
+            self.tagging_directive.to_string(),
+        );
+        headers.extend(self.object.into_headers());
+        headers
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CopyObjectResult {
+    pub etag: String,
+    pub last_modified: Option<DateTime<Utc>>,
+    pub crc64: Option<u64>,
+    pub version_id: Option<String>,
+    /// The version of the source object which is copied.
+    pub source_version_id: Option<String>,
+    pub request_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawCopyResult {
+    #[serde(rename = "ETag", default)]
+    etag: String,
+    #[serde(rename = "LastModified", default)]
+    last_modified: String,
+}
+
+impl RawCopyResult {
+    fn try_from_xml(xml: &[u8]) -> Result<Self> {
+        let c = Cursor::new(xml);
+        let raw: RawCopyResult = quick_xml::de::from_reader(c)?;
+        Ok(raw)
+    }
+}
+
+impl CopyObjectResult {
+    pub(crate) fn try_from_response(headers: &HeaderMap, xml: &[u8]) -> Result<Self> {
+        let raw = RawCopyResult::try_from_xml(xml)?;
+        Ok(CopyObjectResult {
+            etag: raw.etag,
+            last_modified: DateTime::parse_from_rfc3339(&raw.last_modified)
+                .ok()
+                .map(|it| it.with_timezone(&Utc)),
+            crc64: header_str(headers, HEADER_OSS_HASH_CRC64).and_then(|it| it.parse().ok()),
+            version_id: header_str(headers, HEADER_OSS_VERSION_ID),
+            source_version_id: header_str(headers, HEADER_OSS_COPY_SOURCE_VERSION_ID),
+            request_id: header_str(headers, HEADER_OSS_REQUEST_ID).unwrap_or_default(),
+        })
+    }
+
+    fn from_multipart(res: CompleteMultipartUploadResult, source: &HeaderMap) -> Self {
+        CopyObjectResult {
+            etag: res.etag,
+            last_modified: None,
+            crc64: res.crc64,
+            version_id: res.version_id,
+            source_version_id: header_str(source, HEADER_OSS_VERSION_ID),
+            request_id: res.request_id,
+        }
+    }
+}
+
+pub(crate) fn part_info_from_response(part_number: u32, xml: &[u8]) -> Result<PartInfo> {
+    let raw = RawCopyResult::try_from_xml(xml)?;
+    Ok(PartInfo {
+        part_number,
+        etag: raw.etag,
+        crc64: None,
+    })
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Tag {
+    #[serde(rename = "Key", default)]
+    key: String,
+    #[serde(rename = "Value", default)]
+    value: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct TagSet {
+    #[serde(rename = "Tag", default)]
+    tags: Vec<Tag>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Tagging {
+    #[serde(rename = "TagSet", default)]
+    tag_set: TagSet,
+}
+
+fn parse_tagging(xml: &[u8]) -> Result<BTreeMap<String, String>> {
+    let c = Cursor::new(xml);
+    let raw: Tagging = quick_xml::de::from_reader(c)?;
+    Ok(raw
+        .tag_set
+        .tags
+        .into_iter()
+        .map(|it| (it.key, it.value))
+        .collect())
+}
+
+/// Returns the byte ranges of the parts, the part size is enlarged if there are too many parts.
+fn split_ranges(size: u64, part_size: u64) -> Vec<(u64, u64)> {
+    let part_size = part_size.max((size + MAX_PART_NUMBER as u64 - 1) / MAX_PART_NUMBER as u64);
+    let mut ranges = vec![];
+    let mut offset = 0;
+    while offset < size {
+        let end = (offset + part_size).min(size);
+        ranges.push((offset, end - 1));
+        offset = end;
+    }
+    ranges
+}
+
+/// Copies a large object with `UploadPartCopy`, `source_headers` are the headers returned by
+/// the HEAD request of the source object.
+pub(crate) async fn multipart_copy(
+    bucket: &Bucket,
+    source: &Bucket,
+    source_headers: &HeaderMap,
+    source_object: &str,
+    object: &str,
+    options: CopyObjectOptions,
+) -> Result<CopyObjectResult> {
+    let size: u64 = match header_str(source_headers, "content-length") {
+        Some(it) => it.parse()?,
+        None => bail!(
+            "missing content-length of source object '{}'",
+            source_object
+        ),
+    };
+
+    // the metadata and tags are not copied by 'UploadPartCopy', so copy them on initiating
+    let mut object_options = options.object.clone();
+    if options.metadata_directive == CopyDirective::Copy {
+        object_options = object_options.inherit(source_headers);
+    }
+    let tagged = header_str(source_headers, "x-oss-tagging-count")
+        .and_then(|it| it.parse::<u64>().ok())
+        .unwrap_or_default()
+        > 0;
+    if options.tagging_directive == CopyDirective::Copy && tagged {
+        let mut params = options.source_version_params().unwrap_or_default();
+        params.insert("tagging".into(), None);
+        let resp = source
+            .do_request(
+                reqwest::Method::GET,
+                source_object,
+                Some(params),
+                None,
+                Body::empty(),
+            )
+            .await?;
+        let b = resp.bytes().await?;
+        for (k, v) in parse_tagging(&b)? {
+            object_options = object_options.tag(k, v);
+        }
+    }
+
+    let upload_id = bucket
+        .initiate_multipart_upload_with_options(object, object_options.into())
+        .await?;
+
+    let ranges = split_ranges(size, options.part_size);
+    let mut copies = futures::stream::iter(ranges.into_iter().enumerate())
+        .map(|(i, range)| {
+            let upload_id = &upload_id;
+            let options = &options;
+            async move {
+                bucket
+                    .upload_part_copy(
+                        object,
+                        upload_id,
+                        i as u32 + 1,
+                        source_object,
+                        Some(range),
+                        options,
+                    )
+                    .await
+            }
+        })
+        .buffer_unordered(options.parallel);
+
+    let mut parts = vec![];
+    while let Some(res) = copies.next().await {
+        match res {
+            Ok(it) => parts.push(it),
+            Err(e) => {
+                bucket.abort_multipart_upload(object, &upload_id).await.ok();
+                return Err(e);
+            }
+        }
+    }
+    drop(copies);
+
+    match bucket
+        .complete_multipart_upload(object, &upload_id, &parts)
+        .await
+    {
+        Ok(res) => Ok(CopyObjectResult::from_multipart(res, source_headers)),
+        Err(e) => {
+            // the copied parts keep accruing storage until the upload is aborted
+            bucket.abort_multipart_upload(object, &upload_id).await.ok();
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_super {
+    use super::*;
+
+    #[test]
+    fn test_source_headers() {
+        let options = CopyObjectOptions::new()
+            .source_bucket("src")
+            .source_version_id("v1")
+            .if_match("etag");
+        let headers = options.source_headers("dest", "a b/c.txt");
+        assert_eq!(
+            "/src/a%20b%2Fc.txt?versionId=v1",
+            headers.get("x-oss-copy-source").unwrap()
+        );
+        assert_eq!("etag", headers.get("x-oss-copy-source-if-match").unwrap());
+
+        let headers = CopyObjectOptions::new()
+            .metadata_directive(CopyDirective::Replace)
+            .object_options(PutObjectOptions::new().tag("k", "v"))
+            .into_headers("dest", "a.txt");
+        assert_eq!("/dest/a.txt", headers.get("x-oss-copy-source").unwrap());
+        assert_eq!("REPLACE", headers.get("x-oss-metadata-directive").unwrap());
+        assert_eq!("COPY", headers.get("x-oss-tagging-directive").unwrap());
+        assert_eq!("k=v", headers.get("x-oss-tagging").unwrap());
+    }
+
+    #[test]
+    fn test_copy_result() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<CopyObjectResult>
+  <ETag>"5B3C1A2E053D763E1B002CC607C5A0FE"</ETag>
+  <LastModified>2019-04-09T03:45:32.000Z</LastModified>
+</CopyObjectResult>"#;
+        let res = CopyObjectResult::try_from_response(&HeaderMap::new(), xml.as_bytes()).unwrap();
+        assert_eq!("\"5B3C1A2E053D763E1B002CC607C5A0FE\"", res.etag);
+        assert!(res.last_modified.is_some());
+
+        let part = part_info_from_response(2, xml.as_bytes()).unwrap();
+        assert_eq!(2, part.part_number);
+        assert_eq!(res.etag, part.etag);
+    }
+
+    #[test]
+    fn test_parse_tagging() {
+        let xml = r#"<Tagging><TagSet><Tag><Key>a</Key><Value>1</Value></Tag><Tag><Key>b</Key><Value>2</Value></Tag></TagSet></Tagging>"#;
+        let tags = parse_tagging(xml.as_bytes()).unwrap();
+        assert_eq!(2, tags.len());
+        assert_eq!("2", tags["b"]);
+    }
+
+    #[test]
+    fn test_split_ranges() {
+        let ranges = split_ranges(250, 100);
+        assert_eq!(vec![(0, 99), (100, 199), (200, 249)], ranges);
+        assert!(split_ranges(MAX_PART_NUMBER as u64 * 10 + 1, 1).len() <= MAX_PART_NUMBER as usize);
+    }
+}
@@ -0,0 +1,113 @@
+use crc::{Crc, CRC_64_XZ};
+
+// CRC-64/XZ is what OSS names CRC64-ECMA.
+static CRC64: Crc<u64> = Crc::<u64>::new(&CRC_64_XZ);
+
+// the reversed polynomial of CRC64-ECMA
+const POLY: u64 = 0xC96C5795D7870F42;
+
+/// Returns the CRC64 of the bytes.
+pub(crate) fn checksum(b: &[u8]) -> u64 {
+    CRC64.checksum(b)
+}
+
+/// Continues a CRC64 with more bytes, `update(0, b)` is the same as `checksum(b)`.
+pub(crate) fn update(crc: u64, b: &[u8]) -> u64 {
+    // convert the final value back to the raw register, which is reflected by `digest_with_initial`
+    let mut digest = CRC64.digest_with_initial((crc ^ CRC_64_XZ.xorout).reverse_bits());
+    digest.update(b);
+    digest.finalize()
+}
+
+fn gf2_matrix_times(mat: &[u64; 64], mut vec: u64) -> u64 {
+    let mut sum = 0;
+    let mut i = 0;
+    while vec != 0 {
+        if vec & 1 != 0 {
+            sum ^= mat[i];
+        }
+        vec >>= 1;
+        i += 1;
+    }
+    sum
+}
+
+fn gf2_matrix_square(square: &mut [u64; 64], mat: &[u64; 64]) {
+    for n in 0..64 {
+        square[n] = gf2_matrix_times(mat, mat[n]);
+    }
+}
+
+/// Combines two CRC64: `combine(checksum(a), checksum(b), b.len())` is `checksum(a + b)`.
+/// It is the same algorithm as `crc32_combine` of zlib.
+pub(crate) fn combine(mut crc1: u64, crc2: u64, mut len2: u64) -> u64 {
+    if len2 == 0 {
+        return crc1;
+    }
+
+    let mut even = [0u64; 64];
+    let mut odd = [0u64; 64];
+
+    // put operator for one zero bit in odd
+    odd[0] = POLY;
+    let mut row = 1;
+    for it in odd.iter_mut().skip(1) {
+        *it = row;
+        row <<= 1;
+    }
+
+    // put operator for two zero bits in even
+    gf2_matrix_square(&mut even, &odd);
+    // put operator for four zero bits in odd
+    gf2_matrix_square(&mut odd, &even);
+
+    // apply len2 zeros to crc1, the first square puts the operator for one zero byte in even
+    loop {
+        gf2_matrix_square(&mut even, &odd);
+        if len2 & 1 != 0 {
+            crc1 = gf2_matrix_times(&even, crc1);
+        }
+        len2 >>= 1;
+        if len2 == 0 {
+            break;
+        }
+
+        gf2_matrix_square(&mut odd, &even);
+        if len2 & 1 != 0 {
+            crc1 = gf2_matrix_times(&odd, crc1);
+        }
+        len2 >>= 1;
+        if len2 == 0 {
+            break;
+        }
+    }
+
+    crc1 ^ crc2
+}
+
+#[cfg(test)]
+mod test_super {
+    use super::*;
+
+    #[test]
+    fn test_checksum() {
+        assert_eq!(0x995DC9BBDF1939FA, checksum(b"123456789"));
+        assert_eq!(0, checksum(b""));
+    }
+
+    #[test]
+    fn test_update() {
+        let crc = update(0, b"12345");
+        assert_eq!(checksum(b"123456789"), update(crc, b"6789"));
+        assert_eq!(checksum(b"123456789"), update(0, b"123456789"));
+    }
+
+    #[test]
+    fn test_combine() {
+        let (a, b) = (b"hello ".as_ref(), b"world, this is yunoss!".as_ref());
+        let whole = checksum(b"hello world, this is yunoss!");
+        assert_eq!(whole, combine(checksum(a), checksum(b), b.len() as u64));
+        assert_eq!(checksum(a), combine(checksum(a), 0, 0));
+        assert_eq!(checksum(b), combine(0, checksum(b), b.len() as u64));
+    }
+}
@@ -0,0 +1,176 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use once_cell::sync::OnceCell;
+
+use crate::credentials::sts::{
+    sts_endpoint, BackgroundRefresh, StsRequest, DEFAULT_DURATION, DEFAULT_SESSION_NAME,
+    DEFAULT_STS_ENDPOINT,
+};
+use crate::credentials::{Credentials, CredentialsProvider};
+use crate::Result;
+
+/// Assumes a RAM role by STS `AssumeRole` with the credentials of another provider, e.g. the
+/// access key of a RAM user.
+///
+/// The temporary credentials are refreshed in the background before they expire.
+pub struct AssumeRoleCredentials {
+    options: AssumeRoleOptions,
+    refresh: OnceCell<BackgroundRefresh>,
+}
+
+#[derive(Clone)]
+struct AssumeRoleOptions {
+    source: Arc<dyn CredentialsProvider>,
+    role_arn: String,
+    session_name: String,
+    policy: Option<String>,
+    external_id: Option<String>,
+    duration: Duration,
+    endpoint: String,
+}
+
+impl AssumeRoleCredentials {
+    /// Creates the provider which assumes the role, e.g. `acs:ram::123456789012****:role/adminrole`,
+    /// with the credentials of the source provider.
+    pub fn new(source: impl CredentialsProvider + 'static, role_arn: impl Into<String>) -> Self {
+        AssumeRoleCredentials {
+            options: AssumeRoleOptions {
+                source: Arc::new(source),
+                role_arn: role_arn.into(),
+                session_name: DEFAULT_SESSION_NAME.into(),
+                policy: None,
+                external_id: None,
+                duration: DEFAULT_DURATION,
+                endpoint: DEFAULT_STS_ENDPOINT.into(),
+            },
+            refresh: OnceCell::new(),
+        }
+    }
+
+    /// The name of the role session, which is recorded in the audit logs.
+    pub fn session_name(mut self, session_name: impl Into<String>) -> Self {
+        self.options.session_name = session_name.into();
+        self
+    }
+
+    /// The policy which further restricts the permissions of the role.
+    pub fn policy(mut self, policy: impl Into<String>) -> Self {
+        self.options.policy = Some(policy.into());
+        self
+    }
+
+    /// The external id which is required by the trust policy of the role.
+    pub fn external_id(mut self, external_id: impl Into<String>) -> Self {
+        self.options.external_id = Some(external_id.into());
+        self
+    }
+
+    /// How long the credentials are valid, between 15 minutes and the max session duration
+    /// of the role, 1 hour by default.
+    pub fn duration(mut self, duration: Duration) -> Self {
+        self.options.duration = duration;
+        self
+    }
+
+    /// The endpoint of STS, `https://sts.aliyuncs.com` by default.
+    pub fn endpoint(mut self, endpoint: impl AsRef<str>) -> Self {
+        self.options.endpoint = sts_endpoint(endpoint.as_ref());
+        self
+    }
+
+    fn refresh(&self) -> Result<&BackgroundRefresh> {
+        self.refresh.get_or_try_init(|| {
+            Ok(BackgroundRefresh::new(AssumeRole {
+                options: self.options.clone(),
+                client: reqwest::Client::builder().build()?,
+            }))
+        })
+    }
+}
+
+impl std::fmt::Debug for AssumeRoleCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AssumeRoleCredentials")
+            .field("role_arn", &self.options.role_arn)
+            .field("session_name", &self.options.session_name)
+            .field("endpoint", &self.options.endpoint)
+            .finish()
+    }
+}
+
+impl CredentialsProvider for AssumeRoleCredentials {
+    fn credentials(&self) -> BoxFuture<'_, Result<Credentials>> {
+        Box::pin(async move { self.refresh()?.get().await })
+    }
+}
+
+struct AssumeRole {
+    options: AssumeRoleOptions,
+    client: reqwest::Client,
+}
+
+impl AssumeRole {
+    async fn assume_role(&self) -> Result<Credentials> {
+        let options = &self.options;
+        let source = options.source.credentials().await?;
+        let mut req = StsRequest::new("AssumeRole")
+            .param("RoleArn", &options.role_arn)
+            .param("RoleSessionName", &options.session_name)
+            .param("DurationSeconds", options.duration.as_secs().to_string());
+        if let Some(it) = &options.policy {
+            req = req.param("Policy", it);
+        }
+        if let Some(it) = &options.external_id {
+            req = req.param("ExternalId", it);
+        }
+        req.sign(&source)
+            .send(&self.client, &options.endpoint)
+            .await
+    }
+}
+
+impl CredentialsProvider for AssumeRole {
+    fn credentials(&self) -> BoxFuture<'_, Result<Credentials>> {
+        Box::pin(self.assume_role())
+    }
+}
+
+#[cfg(test)]
+mod test_super {
+    use std::collections::BTreeMap;
+
+    use crate::credentials::StaticCredentials;
+    use crate::test_support::serve;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_assume_role() {
+        let (endpoint, requests) = serve(|_| {
+            let body = r#"{"RequestId": "id", "Credentials": {"AccessKeyId": "STS.ak",
+                "AccessKeySecret": "sk", "SecurityToken": "token",
+                "Expiration": "2099-01-01T00:00:00Z"}}"#;
+            (200, body.into())
+        })
+        .await;
+
+        let provider = AssumeRoleCredentials::new(StaticCredentials::new("ak", "sk"), "role")
+            .policy("{}")
+            .duration(Duration::from_secs(900))
+            .endpoint(&endpoint);
+        let cred = provider.credentials().await.unwrap();
+        assert_eq!("STS.ak", cred.access_key_id());
+        assert_eq!(Some("token"), cred.security_token());
+
+        let requests = requests.lock().unwrap();
+        let params: BTreeMap<String, String> =
+            serde_urlencoded::from_str(&requests[0].body).unwrap();
+        assert_eq!("role", params["RoleArn"]);
+        assert_eq!("900", params["DurationSeconds"]);
+        assert_eq!("{}", params["Policy"]);
+        assert_eq!(DEFAULT_SESSION_NAME, params["RoleSessionName"]);
+        assert_eq!("ak", params["AccessKeyId"]);
+    }
+}
@@ -0,0 +1,283 @@
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+use crate::credentials::{
+    Credentials, CredentialsProvider, EcsRamRoleCredentials, EnvCredentials, OidcCredentials,
+    ProfileCredentials, StaticCredentials,
+};
+use crate::Result;
+
+const ENV_ECS_METADATA_DISABLED: &str = "ALIBABA_CLOUD_ECS_METADATA_DISABLED";
+/// The metadata service is unreachable outside ECS, so it is probed with a short timeout.
+const ECS_PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+/// A failed resolution is reported again without trying the sources within this duration.
+const RESOLVE_BACKOFF: Duration = Duration::from_secs(60);
+
+/// The sources of `DefaultCredentialsChain`, in the order they are tried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CredentialsSource {
+    /// The credentials set by `DefaultCredentialsChain::explicit`.
+    Explicit,
+    /// `EnvCredentials`.
+    Environment,
+    /// `OidcCredentials::from_env`.
+    Oidc,
+    /// `ProfileCredentials`.
+    Profile,
+    /// `EcsRamRoleCredentials`, which can be disabled by `ALIBABA_CLOUD_ECS_METADATA_DISABLED`.
+    EcsRamRole,
+}
+
+impl Display for CredentialsSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            CredentialsSource::Explicit => "explicit",
+            CredentialsSource::Environment => "environment",
+            CredentialsSource::Oidc => "oidc",
+            CredentialsSource::Profile => "profile",
+            CredentialsSource::EcsRamRole => "ecs_ram_role",
+        };
+        f.write_str(s)
+    }
+}
+
+type ProviderFactory = Box<dyn Fn() -> Result<Arc<dyn CredentialsProvider>> + Send + Sync>;
+
+/// Resolves the credentials from the first available source: the explicit credentials, the
+/// environment variables, the OIDC token of RRSA, the profile file and the RAM role of ECS.
+///
+/// The source is resolved on the first call and used since then, the skipped sources and the
+/// reasons are kept for troubleshooting. If no source is available, the sources are not tried
+/// again within a minute. It is used by `ClientBuilder::build` if neither the access key nor a
+/// provider is set.
+pub struct DefaultCredentialsChain {
+    sources: Vec<(CredentialsSource, ProviderFactory)>,
+    resolved: Mutex<Resolved>,
+    report: std::sync::Mutex<ChainReport>,
+}
+
+enum Resolved {
+    Pending,
+    Provider(Arc<dyn CredentialsProvider>),
+    // the time and the error of the last failed resolution
+    Failed(Instant, String),
+}
+
+#[derive(Debug, Default)]
+struct ChainReport {
+    source: Option<CredentialsSource>,
+    skipped: Vec<(CredentialsSource, String)>,
+}
+
+impl DefaultCredentialsChain {
+    pub fn new() -> Self {
+        let sources: Vec<(CredentialsSource, ProviderFactory)> = vec![
+            (
+                CredentialsSource::Environment,
+                Box::new(|| Ok(Arc::new(EnvCredentials::new()))),
+            ),
+            (
+                CredentialsSource::Oidc,
+                Box::new(|| Ok(Arc::new(OidcCredentials::from_env()?))),
+            ),
+            (
+                CredentialsSource::Profile,
+                Box::new(|| Ok(Arc::new(ProfileCredentials::new()))),
+            ),
+            (
+                CredentialsSource::EcsRamRole,
+                Box::new(|| {
+                    let disabled = std::env::var(ENV_ECS_METADATA_DISABLED)
+                        .map(|it| it.eq_ignore_ascii_case("true"))
+                        .unwrap_or_default();
+                    if disabled {
+                        bail!("disabled by {}", ENV_ECS_METADATA_DISABLED);
+                    }
+                    Ok(Arc::new(
+                        EcsRamRoleCredentials::new().timeout(ECS_PROBE_TIMEOUT),
+                    ))
+                }),
+            ),
+        ];
+        Self::with_sources(sources)
+    }
+
+    fn with_sources(sources: Vec<(CredentialsSource, ProviderFactory)>) -> Self {
+        DefaultCredentialsChain {
+            sources,
+            resolved: Mutex::new(Resolved::Pending),
+            report: Default::default(),
+        }
+    }
+
+    /// Tries the credentials before any other source.
+    pub fn explicit(mut self, credentials: Credentials) -> Self {
+        let provider: Arc<dyn CredentialsProvider> = Arc::new(StaticCredentials::from(credentials));
+        self.sources.insert(
+            0,
+            (
+                CredentialsSource::Explicit,
+                Box::new(move || Ok(provider.clone())),
+            ),
+        );
+        self
+    }
+
+    /// The source which the credentials are resolved from, `None` if it is not resolved yet.
+    pub fn source(&self) -> Option<CredentialsSource> {
+        self.report.lock().unwrap().source
+    }
+
+    /// The sources tried before the resolved one, and why they are skipped.
+    pub fn skipped(&self) -> Vec<(CredentialsSource, String)> {
+        self.report.lock().unwrap().skipped.clone()
+    }
+
+    async fn resolve(&self) -> Result<Credentials> {
+        let mut resolved = self.resolved.lock().await;
+        match &*resolved {
+            Resolved::Provider(it) => {
+                let provider = it.clone();
+                drop(resolved);
+                return provider.credentials().await;
+            }
+            Resolved::Failed(at, e) if at.elapsed() < RESOLVE_BACKOFF => bail!("{}", e),
+            _ => {}
+        }
+
+        let mut skipped = vec![];
+        for (source, factory) in &self.sources {
+            let res = match factory() {
+                Ok(provider) => provider.credentials().await.map(|it| (provider, it)),
+                Err(e) => Err(e),
+            };
+            match res {
+                Ok((provider, credentials)) => {
+                    info!("resolve credentials from source '{}'", source);
+                    *resolved = Resolved::Provider(provider);
+                    let mut report = self.report.lock().unwrap();
+                    report.source = Some(*source);
+                    report.skipped = skipped;
+                    return Ok(credentials);
+                }
+                Err(e) => {
+                    debug!("skip credentials source '{}': {}", source, e);
+                    skipped.push((*source, e.to_string()));
+                }
+            }
+        }
+
+        let reasons = skipped
+            .iter()
+            .map(|(source, reason)| format!("{}: {}", source, reason))
+            .collect::<Vec<_>>()
+            .join("; ");
+        self.report.lock().unwrap().skipped = skipped;
+        let e = format!("no credentials found in the default chain: {}", reasons);
+        *resolved = Resolved::Failed(Instant::now(), e.clone());
+        bail!(e)
+    }
+}
+
+impl Default for DefaultCredentialsChain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for DefaultCredentialsChain {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DefaultCredentialsChain")
+            .field("report", &self.report)
+            .finish()
+    }
+}
+
+impl CredentialsProvider for DefaultCredentialsChain {
+    fn credentials(&self) -> BoxFuture<'_, Result<Credentials>> {
+        Box::pin(self.resolve())
+    }
+}
+
+#[cfg(test)]
+mod test_super {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+
+    fn failing(reason: &'static str) -> ProviderFactory {
+        Box::new(move || Err(anyhow!(reason)))
+    }
+
+    fn static_credentials(ak: &'static str) -> ProviderFactory {
+        Box::new(move || Ok(Arc::new(StaticCredentials::new(ak, "sk"))))
+    }
+
+    #[tokio::test]
+    async fn test_resolve() {
+        let chain = DefaultCredentialsChain::with_sources(vec![
+            (CredentialsSource::Environment, failing("no env")),
+            (CredentialsSource::Profile, static_credentials("profile")),
+            (CredentialsSource::EcsRamRole, static_credentials("ecs")),
+        ]);
+        assert_eq!(None, chain.source());
+
+        let cred = chain.credentials().await.unwrap();
+        assert_eq!("profile", cred.access_key_id());
+        assert_eq!(Some(CredentialsSource::Profile), chain.source());
+        assert_eq!(
+            vec![(CredentialsSource::Environment, "no env".to_string())],
+            chain.skipped()
+        );
+
+        let chain = DefaultCredentialsChain::with_sources(vec![(
+            CredentialsSource::Environment,
+            failing("no env"),
+        )])
+        .explicit(Credentials::new("explicit", "sk"));
+        assert_eq!(
+            "explicit",
+            chain.credentials().await.unwrap().access_key_id()
+        );
+        assert_eq!(Some(CredentialsSource::Explicit), chain.source());
+        assert!(chain.skipped().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_no_credentials() {
+        let tried = Arc::new(AtomicU32::new(0));
+        let counter = tried.clone();
+        let chain = DefaultCredentialsChain::with_sources(vec![
+            (CredentialsSource::Environment, failing("no env")),
+            (
+                CredentialsSource::Profile,
+                Box::new(move || {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    Err(anyhow!("no profile"))
+                }),
+            ),
+        ]);
+        let e = chain.credentials().await.unwrap_err().to_string();
+        assert!(
+            e.contains("environment: no env; profile: no profile"),
+            "{}",
+            e
+        );
+        assert_eq!(None, chain.source());
+        assert_eq!(2, chain.skipped().len());
+
+        // the failure is reported again without trying the sources
+        let e = chain.credentials().await.unwrap_err().to_string();
+        assert!(e.contains("profile: no profile"), "{}", e);
+        assert_eq!(1, tried.load(Ordering::SeqCst));
+
+        tokio::time::advance(RESOLVE_BACKOFF).await;
+        chain.credentials().await.unwrap_err();
+        assert_eq!(2, tried.load(Ordering::SeqCst));
+    }
+}
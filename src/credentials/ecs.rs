@@ -0,0 +1,299 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use once_cell::sync::OnceCell;
+use serde::Deserialize;
+
+use crate::credentials::{Credentials, CredentialsCache, CredentialsProvider};
+use crate::Result;
+
+const DEFAULT_BASE_URL: &str = "http://100.100.100.200";
+const PATH_TOKEN: &str = "/latest/api/token";
+const PATH_CREDENTIALS: &str = "/latest/meta-data/ram/security-credentials/";
+const HEADER_TOKEN: &str = "x-aliyun-ecs-metadata-token";
+const HEADER_TOKEN_TTL: &str = "x-aliyun-ecs-metadata-token-ttl-seconds";
+const TOKEN_TTL_SECONDS: u64 = 21600;
+const ENV_ROLE_NAME: &str = "ALIBABA_CLOUD_ECS_METADATA";
+const ENV_IMDSV1_DISABLED: &str = "ALIBABA_CLOUD_IMDSV1_DISABLED";
+
+/// Fetches the temporary credentials of the RAM role attached to the ECS instance from the
+/// metadata service.
+///
+/// A session token is requested before reading the metadata (IMDSv2), if that fails the metadata
+/// is read without the token unless `imds_v1_disabled` is set. The credentials are cached and
+/// refreshed before they expire.
+pub struct EcsRamRoleCredentials {
+    metadata: EcsMetadata,
+    cache: OnceCell<CredentialsCache>,
+}
+
+impl EcsRamRoleCredentials {
+    pub fn new() -> Self {
+        let metadata = EcsMetadata {
+            base_url: DEFAULT_BASE_URL.into(),
+            role_name: std::env::var(ENV_ROLE_NAME)
+                .ok()
+                .filter(|it| !it.is_empty()),
+            imds_v1_disabled: std::env::var(ENV_IMDSV1_DISABLED)
+                .map(|it| it.eq_ignore_ascii_case("true"))
+                .unwrap_or_default(),
+            timeout: Duration::from_secs(5),
+            cached_role_name: Default::default(),
+        };
+        EcsRamRoleCredentials {
+            metadata,
+            cache: OnceCell::new(),
+        }
+    }
+
+    /// The name of the RAM role, it is read from the metadata service if not set.
+    /// Defaults to `ALIBABA_CLOUD_ECS_METADATA`.
+    pub fn role_name(mut self, role_name: impl Into<String>) -> Self {
+        self.metadata.role_name = Some(role_name.into());
+        self
+    }
+
+    /// The base url of the metadata service, `http://100.100.100.200` by default.
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.metadata.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Fails instead of reading the metadata without a session token.
+    /// Defaults to `ALIBABA_CLOUD_IMDSV1_DISABLED`.
+    pub fn imds_v1_disabled(mut self, disabled: bool) -> Self {
+        self.metadata.imds_v1_disabled = disabled;
+        self
+    }
+
+    /// The timeout of each request to the metadata service, 5 seconds by default.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.metadata.timeout = timeout;
+        self
+    }
+
+    fn cache(&self) -> Result<&CredentialsCache> {
+        self.cache.get_or_try_init(|| {
+            let metadata = self.metadata.clone();
+            let client = reqwest::Client::builder()
+                .no_proxy()
+                .timeout(metadata.timeout)
+                .build()?;
+            Ok(CredentialsCache::new(Arc::new(EcsMetadataProvider {
+                metadata,
+                client,
+            })))
+        })
+    }
+}
+
+impl Default for EcsRamRoleCredentials {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for EcsRamRoleCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EcsRamRoleCredentials")
+            .field("metadata", &self.metadata)
+            .finish()
+    }
+}
+
+impl CredentialsProvider for EcsRamRoleCredentials {
+    fn credentials(&self) -> BoxFuture<'_, Result<Credentials>> {
+        Box::pin(async move { self.cache()?.get().await })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct EcsMetadata {
+    base_url: String,
+    role_name: Option<String>,
+    imds_v1_disabled: bool,
+    timeout: Duration,
+    cached_role_name: Arc<tokio::sync::OnceCell<String>>,
+}
+
+struct EcsMetadataProvider {
+    metadata: EcsMetadata,
+    client: reqwest::Client,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct EcsCredentialsResponse {
+    #[serde(default)]
+    code: String,
+    #[serde(default)]
+    access_key_id: String,
+    #[serde(default)]
+    access_key_secret: String,
+    #[serde(default)]
+    security_token: String,
+    #[serde(default)]
+    expiration: String,
+}
+
+impl EcsMetadataProvider {
+    async fn fetch(&self) -> Result<Credentials> {
+        let token = self.fetch_token().await?;
+        let role_name = match &self.metadata.role_name {
+            Some(it) => it.clone(),
+            None => self
+                .metadata
+                .cached_role_name
+                .get_or_try_init(|| self.get(PATH_CREDENTIALS, token.as_deref()))
+                .await?
+                .trim()
+                .to_string(),
+        };
+        if role_name.is_empty() {
+            bail!("no RAM role is attached to the ECS instance");
+        }
+
+        let path = format!("{}{}", PATH_CREDENTIALS, role_name);
+        let body = self.get(&path, token.as_deref()).await?;
+        let res: EcsCredentialsResponse = serde_json::from_str(&body)?;
+        if res.code != "Success" {
+            bail!(
+                "get credentials of RAM role '{}' failed: {}",
+                role_name,
+                body
+            );
+        }
+        let expiration = DateTime::parse_from_rfc3339(&res.expiration)?.with_timezone(&Utc);
+        Ok(Credentials::new(res.access_key_id, res.access_key_secret)
+            .with_security_token(res.security_token)
+            .with_expiration(expiration))
+    }
+
+    async fn fetch_token(&self) -> Result<Option<String>> {
+        let url = format!("{}{}", self.metadata.base_url, PATH_TOKEN);
+        let res = self
+            .client
+            .put(url)
+            .header(HEADER_TOKEN_TTL, TOKEN_TTL_SECONDS)
+            .send()
+            .await
+            .and_then(|it| it.error_for_status());
+        let e = match res {
+            Ok(it) => match it.text().await {
+                Ok(token) => return Ok(Some(token)),
+                Err(e) => e,
+            },
+            Err(e) => e,
+        };
+        if self.metadata.imds_v1_disabled {
+            bail!("get metadata token failed: {}", e);
+        }
+        warn!("get metadata token failed, fall back to IMDSv1: {}", e);
+        Ok(None)
+    }
+
+    async fn get(&self, path: &str, token: Option<&str>) -> Result<String> {
+        let url = format!("{}{}", self.metadata.base_url, path);
+        let mut req = self.client.get(url);
+        if let Some(it) = token {
+            req = req.header(HEADER_TOKEN, it);
+        }
+        let res = req.send().await?;
+        let status = res.status();
+        let body = res.text().await?;
+        if !status.is_success() {
+            bail!("get metadata '{}' failed: {} {}", path, status, body);
+        }
+        Ok(body)
+    }
+}
+
+impl CredentialsProvider for EcsMetadataProvider {
+    fn credentials(&self) -> BoxFuture<'_, Result<Credentials>> {
+        Box::pin(self.fetch())
+    }
+}
+
+#[cfg(test)]
+mod test_super {
+    use crate::test_support::serve;
+
+    use super::*;
+
+    const CREDENTIALS: &str = r#"{
+        "AccessKeyId": "STS.ak",
+        "AccessKeySecret": "sk",
+        "Expiration": "2099-01-01T00:00:00Z",
+        "SecurityToken": "token",
+        "LastUpdated": "2023-12-03T12:12:12Z",
+        "Code": "Success"
+    }"#;
+
+    #[tokio::test]
+    async fn test_imds_v2() {
+        let (base_url, requests) = serve(|req| match (req.method.as_str(), req.path.as_str()) {
+            ("PUT", PATH_TOKEN) => (200, "imds-token".into()),
+            (_, _) if req.header(HEADER_TOKEN) != Some("imds-token") => (401, "".into()),
+            ("GET", PATH_CREDENTIALS) => (200, "role\n".into()),
+            ("GET", "/latest/meta-data/ram/security-credentials/role") => (200, CREDENTIALS.into()),
+            _ => (404, "".into()),
+        })
+        .await;
+
+        let provider = EcsRamRoleCredentials::new()
+            .base_url(format!("{}/", base_url))
+            .imds_v1_disabled(true);
+        let cred = provider.credentials().await.unwrap();
+        assert_eq!("STS.ak", cred.access_key_id());
+        assert_eq!("sk", cred.access_key_secret());
+        assert_eq!(Some("token"), cred.security_token());
+        assert_eq!(
+            DateTime::parse_from_rfc3339("2099-01-01T00:00:00Z").unwrap(),
+            cred.expiration().unwrap()
+        );
+
+        // the credentials are cached
+        provider.credentials().await.unwrap();
+        let requests = requests.lock().unwrap();
+        assert_eq!(3, requests.len());
+        assert_eq!(Some("21600"), requests[0].header(HEADER_TOKEN_TTL));
+    }
+
+    #[tokio::test]
+    async fn test_imds_v1() {
+        let (base_url, _) = serve(|req| match (req.method.as_str(), req.path.as_str()) {
+            ("GET", "/latest/meta-data/ram/security-credentials/role") => (200, CREDENTIALS.into()),
+            _ => (403, "".into()),
+        })
+        .await;
+
+        let provider = EcsRamRoleCredentials::new()
+            .base_url(&base_url)
+            .role_name("role")
+            .imds_v1_disabled(false);
+        let cred = provider.credentials().await.unwrap();
+        assert_eq!("STS.ak", cred.access_key_id());
+
+        let provider = EcsRamRoleCredentials::new()
+            .base_url(&base_url)
+            .role_name("role")
+            .imds_v1_disabled(true);
+        assert!(provider.credentials().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_failure_code() {
+        let (base_url, _) = serve(|req| match req.method.as_str() {
+            "GET" => (200, r#"{"Code": "Failure"}"#.into()),
+            _ => (200, "imds-token".into()),
+        })
+        .await;
+
+        let provider = EcsRamRoleCredentials::new()
+            .base_url(&base_url)
+            .role_name("role");
+        assert!(provider.credentials().await.is_err());
+    }
+}
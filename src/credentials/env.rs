@@ -0,0 +1,94 @@
+use futures::future::BoxFuture;
+
+use crate::credentials::{Credentials, CredentialsProvider};
+use crate::Result;
+
+// the variables of OSS take precedence over the ones shared by Alibaba Cloud SDKs
+const ACCESS_KEY_ID: [&str; 2] = ["OSS_ACCESS_KEY_ID", "ALIBABA_CLOUD_ACCESS_KEY_ID"];
+const ACCESS_KEY_SECRET: [&str; 2] = ["OSS_ACCESS_KEY_SECRET", "ALIBABA_CLOUD_ACCESS_KEY_SECRET"];
+const SECURITY_TOKEN: [&str; 2] = ["OSS_SESSION_TOKEN", "ALIBABA_CLOUD_SECURITY_TOKEN"];
+
+/// Reads the credentials from the environment variables:
+///
+/// - `OSS_ACCESS_KEY_ID` or `ALIBABA_CLOUD_ACCESS_KEY_ID`
+/// - `OSS_ACCESS_KEY_SECRET` or `ALIBABA_CLOUD_ACCESS_KEY_SECRET`
+/// - `OSS_SESSION_TOKEN` or `ALIBABA_CLOUD_SECURITY_TOKEN`, which is optional
+///
+/// The credentials have no expiration, so the variables are read once and cached by the client,
+/// a rotated token is not picked up until a new client is built.
+#[derive(Debug, Clone, Default)]
+pub struct EnvCredentials;
+
+impl EnvCredentials {
+    pub fn new() -> Self {
+        EnvCredentials
+    }
+
+    fn load<F>(lookup: F) -> Result<Credentials>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let first = |keys: &[&str]| {
+            keys.iter()
+                .filter_map(|it| lookup(it))
+                .find(|it| !it.is_empty())
+        };
+
+        let access_key_id = match first(&ACCESS_KEY_ID) {
+            Some(it) => it,
+            None => bail!("environment variable {} is not set", ACCESS_KEY_ID[0]),
+        };
+        let access_key_secret = match first(&ACCESS_KEY_SECRET) {
+            Some(it) => it,
+            None => bail!("environment variable {} is not set", ACCESS_KEY_SECRET[0]),
+        };
+        let mut credentials = Credentials::new(access_key_id, access_key_secret);
+        if let Some(it) = first(&SECURITY_TOKEN) {
+            credentials = credentials.with_security_token(it);
+        }
+        Ok(credentials)
+    }
+}
+
+impl CredentialsProvider for EnvCredentials {
+    fn credentials(&self) -> BoxFuture<'_, Result<Credentials>> {
+        Box::pin(async { Self::load(|k| std::env::var(k).ok()) })
+    }
+}
+
+#[cfg(test)]
+mod test_super {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn load(vars: &[(&str, &str)]) -> Result<Credentials> {
+        let vars: HashMap<_, _> = vars.iter().cloned().collect();
+        EnvCredentials::load(|k| vars.get(k).map(|it| it.to_string()))
+    }
+
+    #[test]
+    fn test_load() {
+        let cred = load(&[
+            ("OSS_ACCESS_KEY_ID", "ak"),
+            ("OSS_ACCESS_KEY_SECRET", "sk"),
+            ("ALIBABA_CLOUD_ACCESS_KEY_ID", "other"),
+            ("ALIBABA_CLOUD_SECURITY_TOKEN", "token"),
+        ])
+        .unwrap();
+        assert_eq!("ak", cred.access_key_id());
+        assert_eq!("sk", cred.access_key_secret());
+        assert_eq!(Some("token"), cred.security_token());
+
+        let cred = load(&[
+            ("ALIBABA_CLOUD_ACCESS_KEY_ID", "ak"),
+            ("ALIBABA_CLOUD_ACCESS_KEY_SECRET", "sk"),
+        ])
+        .unwrap();
+        assert_eq!("ak", cred.access_key_id());
+        assert_eq!(None, cred.security_token());
+
+        assert!(load(&[("OSS_ACCESS_KEY_ID", "ak")]).is_err());
+        assert!(load(&[]).is_err());
+    }
+}
@@ -0,0 +1,376 @@
+use std::fmt::{Debug, Formatter};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use tokio::sync::Mutex;
+
+use crate::Result;
+
+pub use self::assume_role::AssumeRoleCredentials;
+pub use self::chain::{CredentialsSource, DefaultCredentialsChain};
+pub use self::ecs::EcsRamRoleCredentials;
+pub use self::env::EnvCredentials;
+pub use self::oidc::OidcCredentials;
+pub use self::profile::ProfileCredentials;
+
+mod assume_role;
+mod chain;
+mod ecs;
+mod env;
+mod oidc;
+mod profile;
+mod sts;
+
+/// The credentials are refreshed if they will expire within this duration, or within half of
+/// their lifetime if it is shorter.
+const REFRESH_AHEAD: Duration = Duration::from_secs(5 * 60);
+
+/// A snapshot of the access key, the security token and the expiration.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Credentials {
+    access_key_id: String,
+    access_key_secret: String,
+    security_token: Option<String>,
+    expiration: Option<DateTime<Utc>>,
+}
+
+impl Credentials {
+    pub fn new(access_key_id: impl Into<String>, access_key_secret: impl Into<String>) -> Self {
+        Credentials {
+            access_key_id: access_key_id.into(),
+            access_key_secret: access_key_secret.into(),
+            security_token: None,
+            expiration: None,
+        }
+    }
+
+    /// Sets the temporary security token of STS.
+    pub fn with_security_token(mut self, token: impl Into<String>) -> Self {
+        self.security_token = Some(token.into()).filter(|it: &String| !it.is_empty());
+        self
+    }
+
+    /// Sets the time when the credentials expire, they never expire by default.
+    pub fn with_expiration(mut self, expiration: DateTime<Utc>) -> Self {
+        self.expiration = Some(expiration);
+        self
+    }
+
+    pub fn access_key_id(&self) -> &str {
+        &self.access_key_id
+    }
+
+    pub fn access_key_secret(&self) -> &str {
+        &self.access_key_secret
+    }
+
+    pub fn security_token(&self) -> Option<&str> {
+        self.security_token.as_deref()
+    }
+
+    pub fn expiration(&self) -> Option<DateTime<Utc>> {
+        self.expiration
+    }
+
+    /// Returns true if the credentials will expire within the duration.
+    pub(crate) fn expires_within(&self, duration: Duration) -> bool {
+        match (self.expiration, chrono::Duration::from_std(duration)) {
+            (Some(expiration), Ok(duration)) => expiration - duration <= Utc::now(),
+            _ => false,
+        }
+    }
+}
+
+impl Debug for Credentials {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // never print the secrets
+        f.debug_struct("Credentials")
+            .field("access_key_id", &self.access_key_id)
+            .field("access_key_secret", &"******")
+            .field(
+                "security_token",
+                &self.security_token.as_ref().map(|_| "******"),
+            )
+            .field("expiration", &self.expiration)
+            .finish()
+    }
+}
+
+/// Provides the credentials used to sign the requests.
+///
+/// The returned credentials are cached by the client until they are about to expire,
+/// so the provider is only called when the credentials need to be refreshed.
+pub trait CredentialsProvider: Send + Sync {
+    fn credentials(&self) -> BoxFuture<'_, Result<Credentials>>;
+}
+
+impl<T: CredentialsProvider + ?Sized> CredentialsProvider for Arc<T> {
+    fn credentials(&self) -> BoxFuture<'_, Result<Credentials>> {
+        (**self).credentials()
+    }
+}
+
+impl Debug for dyn CredentialsProvider {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("CredentialsProvider")
+    }
+}
+
+/// A provider which always returns the same credentials.
+#[derive(Debug, Clone)]
+pub struct StaticCredentials {
+    credentials: Credentials,
+}
+
+impl StaticCredentials {
+    pub fn new(access_key_id: impl Into<String>, access_key_secret: impl Into<String>) -> Self {
+        Credentials::new(access_key_id, access_key_secret).into()
+    }
+
+    /// Creates the provider of temporary STS credentials.
+    pub fn with_token(
+        access_key_id: impl Into<String>,
+        access_key_secret: impl Into<String>,
+        security_token: impl Into<String>,
+    ) -> Self {
+        Credentials::new(access_key_id, access_key_secret)
+            .with_security_token(security_token)
+            .into()
+    }
+}
+
+impl From<Credentials> for StaticCredentials {
+    fn from(value: Credentials) -> Self {
+        StaticCredentials { credentials: value }
+    }
+}
+
+impl CredentialsProvider for StaticCredentials {
+    fn credentials(&self) -> BoxFuture<'_, Result<Credentials>> {
+        let credentials = self.credentials.clone();
+        Box::pin(async move { Ok(credentials) })
+    }
+}
+
+/// Caches the credentials of a provider, and refreshes them before they expire.
+pub(crate) struct CredentialsCache {
+    provider: Arc<dyn CredentialsProvider>,
+    cached: RwLock<Option<CachedCredentials>>,
+    // makes the concurrent requests share one refresh
+    refreshing: Mutex<()>,
+}
+
+#[derive(Clone)]
+struct CachedCredentials {
+    credentials: Credentials,
+    // the credentials which never expire are never refreshed
+    refresh_at: Option<DateTime<Utc>>,
+}
+
+impl CachedCredentials {
+    fn new(credentials: Credentials) -> Self {
+        // the short-lived credentials are refreshed when half of their lifetime is left
+        let refresh_at = credentials.expiration.map(|expiration| {
+            let ahead = chrono::Duration::from_std(REFRESH_AHEAD)
+                .unwrap_or_else(|_| chrono::Duration::zero())
+                .min((expiration - Utc::now()) / 2)
+                .max(chrono::Duration::zero());
+            expiration - ahead
+        });
+        CachedCredentials {
+            credentials,
+            refresh_at,
+        }
+    }
+
+    fn needs_refresh(&self) -> bool {
+        matches!(self.refresh_at, Some(it) if it <= Utc::now())
+    }
+
+    fn is_expired(&self) -> bool {
+        self.credentials.expires_within(Duration::ZERO)
+    }
+}
+
+impl CredentialsCache {
+    pub(crate) fn new(provider: Arc<dyn CredentialsProvider>) -> Self {
+        CredentialsCache {
+            provider,
+            cached: RwLock::new(None),
+            refreshing: Mutex::new(()),
+        }
+    }
+
+    pub(crate) async fn get(&self) -> Result<Credentials> {
+        let cached = self.cached();
+        if let Some(it) = &cached {
+            if !it.needs_refresh() {
+                return Ok(it.credentials.clone());
+            }
+        }
+
+        let _guard = match self.refreshing.try_lock() {
+            Ok(guard) => guard,
+            // another request is refreshing, the old credentials are used until they expire
+            Err(_) => match cached {
+                Some(it) if !it.is_expired() => return Ok(it.credentials),
+                _ => self.refreshing.lock().await,
+            },
+        };
+
+        // the credentials may have been refreshed while waiting for the lock
+        let cached = self.cached();
+        if let Some(it) = &cached {
+            if !it.needs_refresh() {
+                return Ok(it.credentials.clone());
+            }
+        }
+
+        match self.provider.credentials().await {
+            Ok(it) => {
+                *self.cached.write().unwrap() = Some(CachedCredentials::new(it.clone()));
+                Ok(it)
+            }
+            Err(e) => match cached {
+                // keep using the old credentials until they are really expired
+                Some(it) if !it.is_expired() => {
+                    warn!("refresh credentials failed, use the cached one: {}", e);
+                    Ok(it.credentials)
+                }
+                _ => Err(e),
+            },
+        }
+    }
+
+    fn cached(&self) -> Option<CachedCredentials> {
+        self.cached.read().unwrap().clone()
+    }
+}
+
+impl Debug for CredentialsCache {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CredentialsCache").finish()
+    }
+}
+
+#[cfg(test)]
+mod test_super {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use tokio::sync::{Notify, Semaphore};
+
+    use super::*;
+
+    struct CountingProvider {
+        count: AtomicU32,
+        ttl: chrono::Duration,
+    }
+
+    impl CredentialsProvider for CountingProvider {
+        fn credentials(&self) -> BoxFuture<'_, Result<Credentials>> {
+            Box::pin(async move {
+                let n = self.count.fetch_add(1, Ordering::SeqCst);
+                Ok(Credentials::new(format!("ak{}", n), "sk")
+                    .with_expiration(Utc::now() + self.ttl))
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cache() {
+        let cache = CredentialsCache::new(Arc::new(CountingProvider {
+            count: AtomicU32::new(0),
+            ttl: chrono::Duration::hours(1),
+        }));
+        assert_eq!("ak0", cache.get().await.unwrap().access_key_id());
+        assert_eq!("ak0", cache.get().await.unwrap().access_key_id());
+
+        // the refresh ahead is capped by the lifetime of the credentials
+        let cache = CredentialsCache::new(Arc::new(CountingProvider {
+            count: AtomicU32::new(0),
+            ttl: chrono::Duration::minutes(1),
+        }));
+        assert_eq!("ak0", cache.get().await.unwrap().access_key_id());
+        assert_eq!("ak0", cache.get().await.unwrap().access_key_id());
+
+        // expired credentials are refreshed every time
+        let cache = CredentialsCache::new(Arc::new(CountingProvider {
+            count: AtomicU32::new(0),
+            ttl: chrono::Duration::zero(),
+        }));
+        assert_eq!("ak0", cache.get().await.unwrap().access_key_id());
+        assert_eq!("ak1", cache.get().await.unwrap().access_key_id());
+    }
+
+    struct BlockingProvider {
+        started: Notify,
+        release: Semaphore,
+    }
+
+    impl CredentialsProvider for BlockingProvider {
+        fn credentials(&self) -> BoxFuture<'_, Result<Credentials>> {
+            Box::pin(async move {
+                self.started.notify_one();
+                self.release.acquire().await.unwrap().forget();
+                Ok(Credentials::new("new", "sk"))
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cache_refreshing() {
+        let provider = Arc::new(BlockingProvider {
+            started: Notify::new(),
+            release: Semaphore::new(0),
+        });
+        let cache = Arc::new(CredentialsCache::new(provider.clone()));
+        let old =
+            Credentials::new("old", "sk").with_expiration(Utc::now() + chrono::Duration::hours(1));
+        *cache.cached.write().unwrap() = Some(CachedCredentials {
+            credentials: old,
+            refresh_at: Some(Utc::now()),
+        });
+
+        let refresh = {
+            let cache = cache.clone();
+            tokio::spawn(async move { cache.get().await })
+        };
+        provider.started.notified().await;
+
+        // the other requests are not blocked by the refresh
+        assert_eq!("old", cache.get().await.unwrap().access_key_id());
+
+        provider.release.add_permits(1);
+        assert_eq!("new", refresh.await.unwrap().unwrap().access_key_id());
+        assert_eq!("new", cache.get().await.unwrap().access_key_id());
+    }
+
+    #[tokio::test]
+    async fn test_static_credentials() {
+        let cred = StaticCredentials::with_token("ak", "sk", "token")
+            .credentials()
+            .await
+            .unwrap();
+        assert_eq!(Some("token"), cred.security_token());
+        assert_eq!(None, cred.expiration());
+
+        let cred = StaticCredentials::with_token("ak", "sk", "")
+            .credentials()
+            .await
+            .unwrap();
+        assert_eq!(None, cred.security_token());
+    }
+
+    #[test]
+    fn test_debug() {
+        let s = format!(
+            "{:?}",
+            Credentials::new("AKID", "SKVALUE").with_security_token("STSVALUE")
+        );
+        assert!(s.contains("AKID"));
+        assert!(!s.contains("SKVALUE"));
+        assert!(!s.contains("STSVALUE"));
+    }
+}
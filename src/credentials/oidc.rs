@@ -0,0 +1,195 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use once_cell::sync::OnceCell;
+
+use crate::credentials::sts::{
+    sts_endpoint, BackgroundRefresh, StsRequest, DEFAULT_DURATION, DEFAULT_SESSION_NAME,
+    DEFAULT_STS_ENDPOINT,
+};
+use crate::credentials::{Credentials, CredentialsProvider};
+use crate::Result;
+
+const ENV_ROLE_ARN: &str = "ALIBABA_CLOUD_ROLE_ARN";
+const ENV_OIDC_PROVIDER_ARN: &str = "ALIBABA_CLOUD_OIDC_PROVIDER_ARN";
+const ENV_OIDC_TOKEN_FILE: &str = "ALIBABA_CLOUD_OIDC_TOKEN_FILE";
+const ENV_ROLE_SESSION_NAME: &str = "ALIBABA_CLOUD_ROLE_SESSION_NAME";
+
+/// Assumes a RAM role by STS `AssumeRoleWithOIDC` with the OIDC token of the workload,
+/// which is how RRSA (RAM Roles for Service Accounts) works on ACK.
+///
+/// The token file is re-read on each refresh since it is rotated by the cluster, and the
+/// temporary credentials are refreshed in the background before they expire.
+pub struct OidcCredentials {
+    options: OidcOptions,
+    refresh: OnceCell<BackgroundRefresh>,
+}
+
+#[derive(Debug, Clone)]
+struct OidcOptions {
+    role_arn: String,
+    oidc_provider_arn: String,
+    token_file: PathBuf,
+    session_name: String,
+    policy: Option<String>,
+    duration: Duration,
+    endpoint: String,
+}
+
+impl OidcCredentials {
+    pub fn new(
+        role_arn: impl Into<String>,
+        oidc_provider_arn: impl Into<String>,
+        token_file: impl Into<PathBuf>,
+    ) -> Self {
+        OidcCredentials {
+            options: OidcOptions {
+                role_arn: role_arn.into(),
+                oidc_provider_arn: oidc_provider_arn.into(),
+                token_file: token_file.into(),
+                session_name: DEFAULT_SESSION_NAME.into(),
+                policy: None,
+                duration: DEFAULT_DURATION,
+                endpoint: DEFAULT_STS_ENDPOINT.into(),
+            },
+            refresh: OnceCell::new(),
+        }
+    }
+
+    /// Creates the provider from `ALIBABA_CLOUD_ROLE_ARN`, `ALIBABA_CLOUD_OIDC_PROVIDER_ARN`,
+    /// `ALIBABA_CLOUD_OIDC_TOKEN_FILE` and the optional `ALIBABA_CLOUD_ROLE_SESSION_NAME`,
+    /// which are injected into the pods by RRSA.
+    pub fn from_env() -> Result<Self> {
+        let get_env = |k: &str| match std::env::var(k) {
+            Ok(it) if !it.is_empty() => Ok(it),
+            _ => Err(anyhow!("environment variable {} is not set", k)),
+        };
+        let mut provider = Self::new(
+            get_env(ENV_ROLE_ARN)?,
+            get_env(ENV_OIDC_PROVIDER_ARN)?,
+            get_env(ENV_OIDC_TOKEN_FILE)?,
+        );
+        if let Ok(it) = get_env(ENV_ROLE_SESSION_NAME) {
+            provider = provider.session_name(it);
+        }
+        Ok(provider)
+    }
+
+    /// The name of the role session, which is recorded in the audit logs.
+    pub fn session_name(mut self, session_name: impl Into<String>) -> Self {
+        self.options.session_name = session_name.into();
+        self
+    }
+
+    /// The policy which further restricts the permissions of the role.
+    pub fn policy(mut self, policy: impl Into<String>) -> Self {
+        self.options.policy = Some(policy.into());
+        self
+    }
+
+    /// How long the credentials are valid, between 15 minutes and the max session duration
+    /// of the role, 1 hour by default.
+    pub fn duration(mut self, duration: Duration) -> Self {
+        self.options.duration = duration;
+        self
+    }
+
+    /// The endpoint of STS, `https://sts.aliyuncs.com` by default.
+    pub fn endpoint(mut self, endpoint: impl AsRef<str>) -> Self {
+        self.options.endpoint = sts_endpoint(endpoint.as_ref());
+        self
+    }
+
+    fn refresh(&self) -> Result<&BackgroundRefresh> {
+        self.refresh.get_or_try_init(|| {
+            Ok(BackgroundRefresh::new(AssumeRoleWithOidc {
+                options: self.options.clone(),
+                client: reqwest::Client::builder().build()?,
+            }))
+        })
+    }
+}
+
+impl std::fmt::Debug for OidcCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OidcCredentials")
+            .field("options", &self.options)
+            .finish()
+    }
+}
+
+impl CredentialsProvider for OidcCredentials {
+    fn credentials(&self) -> BoxFuture<'_, Result<Credentials>> {
+        Box::pin(async move { self.refresh()?.get().await })
+    }
+}
+
+struct AssumeRoleWithOidc {
+    options: OidcOptions,
+    client: reqwest::Client,
+}
+
+impl AssumeRoleWithOidc {
+    async fn assume_role(&self) -> Result<Credentials> {
+        let options = &self.options;
+        let token = tokio::fs::read_to_string(&options.token_file).await?;
+        // the request is authenticated by the token instead of a signature
+        let mut req = StsRequest::new("AssumeRoleWithOIDC")
+            .param("RoleArn", &options.role_arn)
+            .param("OIDCProviderArn", &options.oidc_provider_arn)
+            .param("OIDCToken", token.trim())
+            .param("RoleSessionName", &options.session_name)
+            .param("DurationSeconds", options.duration.as_secs().to_string());
+        if let Some(it) = &options.policy {
+            req = req.param("Policy", it);
+        }
+        req.send(&self.client, &options.endpoint).await
+    }
+}
+
+impl CredentialsProvider for AssumeRoleWithOidc {
+    fn credentials(&self) -> BoxFuture<'_, Result<Credentials>> {
+        Box::pin(self.assume_role())
+    }
+}
+
+#[cfg(test)]
+mod test_super {
+    use std::collections::BTreeMap;
+    use std::io::Write;
+
+    use crate::test_support::serve;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_assume_role_with_oidc() {
+        let (endpoint, requests) = serve(|_| {
+            let body = r#"{"RequestId": "id", "Credentials": {"AccessKeyId": "STS.ak",
+                "AccessKeySecret": "sk", "SecurityToken": "token",
+                "Expiration": "2099-01-01T00:00:00Z"}}"#;
+            (200, body.into())
+        })
+        .await;
+        let mut token_file = tempfile::NamedTempFile::new().unwrap();
+        token_file.write_all(b"oidc-token\n").unwrap();
+
+        let provider = OidcCredentials::new("role", "provider", token_file.path())
+            .session_name("test")
+            .endpoint(&endpoint);
+        let cred = provider.credentials().await.unwrap();
+        assert_eq!("STS.ak", cred.access_key_id());
+        provider.credentials().await.unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(1, requests.len());
+        let params: BTreeMap<String, String> =
+            serde_urlencoded::from_str(&requests[0].body).unwrap();
+        assert_eq!("AssumeRoleWithOIDC", params["Action"]);
+        assert_eq!("oidc-token", params["OIDCToken"]);
+        assert_eq!("provider", params["OIDCProviderArn"]);
+        assert_eq!("test", params["RoleSessionName"]);
+        assert!(!params.contains_key("Signature"));
+    }
+}
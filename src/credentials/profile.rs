@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use futures::future::BoxFuture;
+use serde::Deserialize;
+
+use crate::credentials::{Credentials, CredentialsProvider};
+use crate::Result;
+
+const ENV_PROFILE: &str = "ALIBABA_CLOUD_PROFILE";
+const ENV_CREDENTIALS_FILE: &str = "ALIBABA_CLOUD_CREDENTIALS_FILE";
+const DEFAULT_PROFILE: &str = "default";
+
+/// Loads the credentials of a profile from the configuration of Alibaba Cloud CLI
+/// (`~/.aliyun/config.json`) or the ini-style credentials file (`~/.alibabacloud/credentials`,
+/// which can be changed by `ALIBABA_CLOUD_CREDENTIALS_FILE`).
+///
+/// The profile is the one set by `profile`, or `ALIBABA_CLOUD_PROFILE`, or the current profile
+/// of the CLI, or `default`. Only the profiles with the access key or the STS token are supported.
+#[derive(Debug, Clone, Default)]
+pub struct ProfileCredentials {
+    profile: Option<String>,
+    config_file: Option<PathBuf>,
+    credentials_file: Option<PathBuf>,
+}
+
+impl ProfileCredentials {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn profile(mut self, profile: impl Into<String>) -> Self {
+        self.profile = Some(profile.into());
+        self
+    }
+
+    /// The path of the CLI configuration, `~/.aliyun/config.json` by default.
+    pub fn config_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config_file = Some(path.into());
+        self
+    }
+
+    /// The path of the ini-style credentials file, `~/.alibabacloud/credentials` by default.
+    pub fn credentials_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.credentials_file = Some(path.into());
+        self
+    }
+
+    fn profile_name(&self) -> Option<String> {
+        self.profile
+            .clone()
+            .or_else(|| std::env::var(ENV_PROFILE).ok())
+            .filter(|it| !it.is_empty())
+    }
+
+    fn config_path(&self) -> Option<PathBuf> {
+        self.config_file
+            .clone()
+            .or_else(|| home_dir().map(|it| it.join(".aliyun").join("config.json")))
+    }
+
+    fn credentials_path(&self) -> Option<PathBuf> {
+        self.credentials_file
+            .clone()
+            .or_else(|| std::env::var_os(ENV_CREDENTIALS_FILE).map(PathBuf::from))
+            .or_else(|| home_dir().map(|it| it.join(".alibabacloud").join("credentials")))
+    }
+
+    async fn load(&self) -> Result<Credentials> {
+        let profile = self.profile_name();
+        if let Some(path) = self.config_path().filter(|it| it.exists()) {
+            let content = tokio::fs::read(&path).await?;
+            let config = CliConfig::from_json(&content)?;
+            if let Some(it) = config.credentials(profile.as_deref())? {
+                return Ok(it);
+            }
+            debug!("profile not found in {}", path.display());
+        }
+        if let Some(path) = self.credentials_path().filter(|it| it.exists()) {
+            let content = tokio::fs::read_to_string(&path).await?;
+            let profile = profile.as_deref().unwrap_or(DEFAULT_PROFILE);
+            if let Some(it) = ini_credentials(&content, profile)? {
+                return Ok(it);
+            }
+            debug!("profile not found in {}", path.display());
+        }
+        bail!(
+            "profile '{}' is not found",
+            profile.as_deref().unwrap_or(DEFAULT_PROFILE)
+        )
+    }
+}
+
+impl CredentialsProvider for ProfileCredentials {
+    fn credentials(&self) -> BoxFuture<'_, Result<Credentials>> {
+        Box::pin(self.load())
+    }
+}
+
+fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .filter(|it| !it.is_empty())
+        .map(PathBuf::from)
+}
+
+/// The `config.json` of Alibaba Cloud CLI.
+#[derive(Debug, Deserialize)]
+struct CliConfig {
+    #[serde(default)]
+    current: String,
+    #[serde(default)]
+    profiles: Vec<CliProfile>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CliProfile {
+    name: String,
+    #[serde(default)]
+    mode: String,
+    #[serde(default)]
+    access_key_id: String,
+    #[serde(default)]
+    access_key_secret: String,
+    #[serde(default)]
+    sts_token: String,
+}
+
+impl CliConfig {
+    fn from_json(content: &[u8]) -> Result<Self> {
+        Ok(serde_json::from_slice(content)?)
+    }
+
+    fn credentials(&self, profile: Option<&str>) -> Result<Option<Credentials>> {
+        let name = match profile {
+            Some(it) => it,
+            None if !self.current.is_empty() => &self.current,
+            None => DEFAULT_PROFILE,
+        };
+        let profile = match self.profiles.iter().find(|it| it.name == name) {
+            Some(it) => it,
+            None => return Ok(None),
+        };
+        let credentials = Credentials::new(&profile.access_key_id, &profile.access_key_secret);
+        let credentials = match profile.mode.as_str() {
+            "AK" | "" => credentials,
+            "StsToken" => credentials.with_security_token(&profile.sts_token),
+            mode => bail!("mode '{}' of profile '{}' is not supported", mode, name),
+        };
+        check_keys(credentials, name).map(Some)
+    }
+}
+
+/// Parses the ini-style credentials file, e.g.
+///
+/// ```ini
+/// [default]
+/// type = access_key
+/// access_key_id = foo
+/// access_key_secret = bar
+/// ```
+fn ini_credentials(content: &str, profile: &str) -> Result<Option<Credentials>> {
+    let sections = parse_ini(content);
+    let section = match sections.get(profile) {
+        Some(it) => it,
+        None => return Ok(None),
+    };
+    let get = |k: &str| section.get(k).map(String::as_str).unwrap_or_default();
+    let credentials = Credentials::new(get("access_key_id"), get("access_key_secret"));
+    let credentials = match get("type") {
+        "access_key" | "" => credentials,
+        "sts" => credentials.with_security_token(get("security_token")),
+        t => bail!("type '{}' of profile '{}' is not supported", t, profile),
+    };
+    check_keys(credentials, profile).map(Some)
+}
+
+fn parse_ini(content: &str) -> HashMap<String, HashMap<String, String>> {
+    let mut sections: HashMap<String, HashMap<String, String>> = HashMap::new();
+    let mut current = None;
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|it| it.strip_suffix(']')) {
+            let name = name.trim().to_string();
+            sections.entry(name.clone()).or_default();
+            current = Some(name);
+        } else if let (Some(name), Some((k, v))) = (&current, line.split_once('=')) {
+            let section = sections.entry(name.clone()).or_default();
+            section.insert(k.trim().to_string(), v.trim().to_string());
+        }
+    }
+    sections
+}
+
+fn check_keys(credentials: Credentials, profile: &str) -> Result<Credentials> {
+    if credentials.access_key_id().is_empty() || credentials.access_key_secret().is_empty() {
+        bail!("access key of profile '{}' is empty", profile);
+    }
+    Ok(credentials)
+}
+
+#[cfg(test)]
+mod test_super {
+    use std::io::Write;
+
+    use tempfile::NamedTempFile;
+
+    use super::*;
+
+    const CONFIG: &str = r#"{
+        "current": "sts",
+        "profiles": [
+            {"name": "default", "mode": "AK", "access_key_id": "ak", "access_key_secret": "sk"},
+            {"name": "sts", "mode": "StsToken", "access_key_id": "ak2", "access_key_secret": "sk2",
+             "sts_token": "token"},
+            {"name": "ecs", "mode": "EcsRamRole", "ram_role_name": "role"}
+        ]
+    }"#;
+
+    const INI: &str = "
+# comment
+[default]
+type = access_key
+access_key_id = ak
+access_key_secret = sk
+
+[project]
+type = sts
+access_key_id = ak3
+access_key_secret = sk3
+security_token = token3
+";
+
+    fn temp_file(content: &str) -> NamedTempFile {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+        file
+    }
+
+    #[test]
+    fn test_cli_config() {
+        let config = CliConfig::from_json(CONFIG.as_bytes()).unwrap();
+        let cred = config.credentials(None).unwrap().unwrap();
+        assert_eq!("ak2", cred.access_key_id());
+        assert_eq!(Some("token"), cred.security_token());
+
+        let cred = config.credentials(Some("default")).unwrap().unwrap();
+        assert_eq!("ak", cred.access_key_id());
+        assert_eq!(None, cred.security_token());
+
+        assert!(config.credentials(Some("ecs")).is_err());
+        assert!(config.credentials(Some("none")).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_ini_credentials() {
+        let cred = ini_credentials(INI, "default").unwrap().unwrap();
+        assert_eq!("ak", cred.access_key_id());
+        assert_eq!("sk", cred.access_key_secret());
+
+        let cred = ini_credentials(INI, "project").unwrap().unwrap();
+        assert_eq!("ak3", cred.access_key_id());
+        assert_eq!(Some("token3"), cred.security_token());
+
+        assert!(ini_credentials(INI, "none").unwrap().is_none());
+        assert!(ini_credentials("[default]\ntype = ecs_ram_role", "default").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_profile_credentials() {
+        let config = temp_file(CONFIG);
+        let ini = temp_file(INI);
+        let provider = ProfileCredentials::new()
+            .config_file(config.path())
+            .credentials_file(ini.path());
+
+        let cred = provider.clone().profile("default").load().await.unwrap();
+        assert_eq!("ak", cred.access_key_id());
+
+        // falls back to the credentials file
+        let cred = provider.clone().profile("project").load().await.unwrap();
+        assert_eq!("ak3", cred.access_key_id());
+
+        assert!(provider.profile("none").load().await.is_err());
+    }
+}
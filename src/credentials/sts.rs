@@ -0,0 +1,361 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha1::Sha1;
+use tokio::sync::Mutex;
+
+use crate::credentials::{Credentials, CredentialsProvider, REFRESH_AHEAD};
+use crate::util;
+use crate::Result;
+
+type HmacSha1 = Hmac<Sha1>;
+
+pub(crate) const DEFAULT_STS_ENDPOINT: &str = "https://sts.aliyuncs.com";
+pub(crate) const DEFAULT_SESSION_NAME: &str = "yunoss-session";
+pub(crate) const DEFAULT_DURATION: Duration = Duration::from_secs(3600);
+const STS_VERSION: &str = "2015-04-01";
+
+/// The credentials are refreshed in the background this long before they expire.
+const BACKGROUND_REFRESH_AHEAD: Duration = Duration::from_secs(10 * 60);
+/// The delay before retrying a failed background refresh.
+const BACKGROUND_RETRY_DELAY: Duration = Duration::from_secs(10);
+
+/// Normalizes the STS endpoint, the scheme is https if it is absent.
+pub(crate) fn sts_endpoint(endpoint: &str) -> String {
+    let endpoint = endpoint.trim_end_matches('/');
+    if endpoint.contains("://") {
+        endpoint.to_string()
+    } else {
+        format!("https://{}", endpoint)
+    }
+}
+
+/// A request of the STS RPC API, which is sent as a form by POST.
+pub(crate) struct StsRequest {
+    params: BTreeMap<String, String>,
+}
+
+impl StsRequest {
+    pub(crate) fn new(action: &str) -> Self {
+        let mut params = BTreeMap::new();
+        params.insert("Action".into(), action.into());
+        params.insert("Format".into(), "JSON".into());
+        params.insert("Version".into(), STS_VERSION.into());
+        params.insert(
+            "Timestamp".into(),
+            Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+        );
+        StsRequest { params }
+    }
+
+    pub(crate) fn param(mut self, key: &str, value: impl Into<String>) -> Self {
+        self.params.insert(key.into(), value.into());
+        self
+    }
+
+    /// Signs the request with the RPC signature (HMAC-SHA1).
+    pub(crate) fn sign(mut self, cred: &Credentials) -> Self {
+        self.params
+            .insert("AccessKeyId".into(), cred.access_key_id().into());
+        if let Some(it) = cred.security_token() {
+            self.params.insert("SecurityToken".into(), it.into());
+        }
+        self.params
+            .insert("SignatureMethod".into(), "HMAC-SHA1".into());
+        self.params.insert("SignatureVersion".into(), "1.0".into());
+        self.params.insert("SignatureNonce".into(), nonce());
+
+        let signature = rpc_signature("POST", &self.params, cred.access_key_secret());
+        self.params.insert("Signature".into(), signature);
+        self
+    }
+
+    pub(crate) async fn send(
+        self,
+        client: &reqwest::Client,
+        endpoint: &str,
+    ) -> Result<Credentials> {
+        let res = client
+            .post(format!("{}/", endpoint))
+            .form(&self.params)
+            .send()
+            .await?;
+        let status = res.status();
+        let body = res.bytes().await?;
+        let res: StsResponse = match serde_json::from_slice(&body) {
+            Ok(it) => it,
+            Err(_) => bail!(
+                "invalid STS response: {} {}",
+                status,
+                String::from_utf8_lossy(&body)
+            ),
+        };
+        match res.credentials {
+            Some(it) if status.is_success() => {
+                let expiration = DateTime::parse_from_rfc3339(&it.expiration)?;
+                Ok(Credentials::new(it.access_key_id, it.access_key_secret)
+                    .with_security_token(it.security_token)
+                    .with_expiration(expiration.with_timezone(&Utc)))
+            }
+            _ => bail!(
+                "{} failed: status={}, code={}, message={}, request_id={}",
+                self.params["Action"],
+                status,
+                res.code,
+                res.message,
+                res.request_id
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct StsResponse {
+    #[serde(default)]
+    request_id: String,
+    #[serde(default)]
+    code: String,
+    #[serde(default)]
+    message: String,
+    credentials: Option<StsCredentials>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct StsCredentials {
+    access_key_id: String,
+    access_key_secret: String,
+    #[serde(default)]
+    security_token: String,
+    expiration: String,
+}
+
+fn rpc_signature(method: &str, params: &BTreeMap<String, String>, secret: &str) -> String {
+    let query = params
+        .iter()
+        .map(|(k, v)| {
+            format!(
+                "{}={}",
+                util::uri_encode(k, false),
+                util::uri_encode(v, false)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("&");
+    let sign_str = format!("{}&%2F&{}", method, util::uri_encode(&query, false));
+    let mut mac = HmacSha1::new_from_slice(format!("{}&", secret).as_bytes())
+        .expect("HMAC can take key of any size");
+    mac.update(sign_str.as_bytes());
+    STANDARD.encode(mac.finalize().into_bytes())
+}
+
+fn nonce() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let now = Utc::now();
+    format!(
+        "{}{:09}-{}-{}",
+        now.timestamp(),
+        now.timestamp_subsec_nanos(),
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Caches the credentials of a provider, and refreshes them in a background task before they
+/// expire, so that the requests are not blocked by the refresh.
+pub(crate) struct BackgroundRefresh {
+    inner: Arc<RefreshInner>,
+}
+
+struct RefreshInner {
+    provider: Box<dyn CredentialsProvider>,
+    cached: Mutex<Option<Credentials>>,
+    started: AtomicBool,
+}
+
+impl BackgroundRefresh {
+    pub(crate) fn new(provider: impl CredentialsProvider + 'static) -> Self {
+        BackgroundRefresh {
+            inner: Arc::new(RefreshInner {
+                provider: Box::new(provider),
+                cached: Mutex::new(None),
+                started: AtomicBool::new(false),
+            }),
+        }
+    }
+
+    pub(crate) async fn get(&self) -> Result<Credentials> {
+        let mut cached = self.inner.cached.lock().await;
+        if let Some(it) = cached.as_ref() {
+            if !it.expires_within(REFRESH_AHEAD) {
+                return Ok(it.clone());
+            }
+        }
+        // the background refresh is late or failed, refresh them now
+        let credentials = self.inner.provider.credentials().await?;
+        *cached = Some(credentials.clone());
+        drop(cached);
+
+        if !self.inner.started.swap(true, Ordering::SeqCst) {
+            let weak = Arc::downgrade(&self.inner);
+            tokio::spawn(refresh_loop(weak, credentials.expiration()));
+        }
+        Ok(credentials)
+    }
+}
+
+/// Refreshes the credentials before they expire, until the provider is dropped.
+async fn refresh_loop(inner: Weak<RefreshInner>, mut expiration: Option<DateTime<Utc>>) {
+    loop {
+        let remaining = match expiration {
+            Some(it) => (it - Utc::now()).to_std().unwrap_or_default(),
+            // the credentials never expire
+            None => return,
+        };
+        let delay = if remaining > BACKGROUND_REFRESH_AHEAD * 2 {
+            remaining - BACKGROUND_REFRESH_AHEAD
+        } else {
+            remaining / 2
+        };
+        tokio::time::sleep(delay.max(Duration::from_secs(1))).await;
+
+        let shared = match inner.upgrade() {
+            Some(it) => it,
+            None => return,
+        };
+        match shared.provider.credentials().await {
+            Ok(it) => {
+                debug!("refresh credentials in the background: {:?}", it);
+                expiration = it.expiration();
+                *shared.cached.lock().await = Some(it);
+            }
+            Err(e) => {
+                warn!("refresh credentials in the background failed: {}", e);
+                tokio::time::sleep(BACKGROUND_RETRY_DELAY).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_super {
+    use std::sync::atomic::AtomicU32;
+
+    use crate::test_support::serve;
+
+    use super::*;
+
+    #[test]
+    fn test_rpc_signature() {
+        // the example of the RPC signature in the docs
+        let params: BTreeMap<String, String> = [
+            ("Timestamp", "2016-02-23T12:46:24Z"),
+            ("Format", "XML"),
+            ("AccessKeyId", "testid"),
+            ("Action", "DescribeRegions"),
+            ("SignatureMethod", "HMAC-SHA1"),
+            ("SignatureNonce", "3ee8c1b8-83d3-44af-a94f-4e0ad82fd6cf"),
+            ("Version", "2014-05-26"),
+            ("SignatureVersion", "1.0"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        assert_eq!(
+            "OLeaidS1JvxuMvnyHOwuJ+uX5qY=",
+            rpc_signature("GET", &params, "testsecret")
+        );
+    }
+
+    #[test]
+    fn test_sts_endpoint() {
+        assert_eq!("https://sts.aliyuncs.com", sts_endpoint("sts.aliyuncs.com"));
+        assert_eq!("http://127.0.0.1:80", sts_endpoint("http://127.0.0.1:80/"));
+    }
+
+    #[tokio::test]
+    async fn test_send() {
+        let (endpoint, requests) = serve(|req| {
+            if req.body.contains("RoleArn=bad") {
+                return (
+                    403,
+                    r#"{"RequestId": "id", "Code": "NoPermission", "Message": "denied"}"#.into(),
+                );
+            }
+            let body = r#"{"RequestId": "id", "Credentials": {"AccessKeyId": "STS.ak",
+                "AccessKeySecret": "sk", "SecurityToken": "token",
+                "Expiration": "2099-01-01T00:00:00Z"}}"#;
+            (200, body.into())
+        })
+        .await;
+
+        let client = reqwest::Client::new();
+        let cred = StsRequest::new("AssumeRole")
+            .param("RoleArn", "role")
+            .sign(&Credentials::new("ak", "sk").with_security_token("source"))
+            .send(&client, &endpoint)
+            .await
+            .unwrap();
+        assert_eq!("STS.ak", cred.access_key_id());
+        assert_eq!(Some("token"), cred.security_token());
+        assert!(cred.expiration().is_some());
+
+        let req = requests.lock().unwrap()[0].clone();
+        assert_eq!("POST", req.method);
+        let params: BTreeMap<String, String> = serde_urlencoded::from_str(&req.body).unwrap();
+        assert_eq!("AssumeRole", params["Action"]);
+        assert_eq!("ak", params["AccessKeyId"]);
+        assert_eq!("source", params["SecurityToken"]);
+        assert!(params.contains_key("Signature"));
+
+        let e = StsRequest::new("AssumeRole")
+            .param("RoleArn", "bad")
+            .send(&client, &endpoint)
+            .await
+            .unwrap_err();
+        assert!(e.to_string().contains("NoPermission"), "{}", e);
+    }
+
+    struct ShortLived {
+        count: Arc<AtomicU32>,
+    }
+
+    impl CredentialsProvider for ShortLived {
+        fn credentials(&self) -> futures::future::BoxFuture<'_, Result<Credentials>> {
+            Box::pin(async move {
+                let n = self.count.fetch_add(1, Ordering::SeqCst);
+                Ok(Credentials::new(format!("ak{}", n), "sk")
+                    .with_expiration(Utc::now() + chrono::Duration::seconds(2)))
+            })
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_background_refresh() {
+        let count = Arc::new(AtomicU32::new(0));
+        let refresh = BackgroundRefresh::new(ShortLived {
+            count: count.clone(),
+        });
+        assert_eq!("ak0", refresh.get().await.unwrap().access_key_id());
+        // the yields let the background task run on the paused clock
+        tokio::task::yield_now().await;
+
+        // the credentials are refreshed when half of their lifetime is left
+        tokio::time::advance(Duration::from_millis(1500)).await;
+        tokio::task::yield_now().await;
+        assert_eq!(2, count.load(Ordering::SeqCst));
+
+        // the background task stops when the provider is dropped
+        drop(refresh);
+        tokio::time::advance(Duration::from_secs(3)).await;
+        tokio::task::yield_now().await;
+        assert_eq!(2, count.load(Ordering::SeqCst));
+    }
+}
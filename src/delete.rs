@@ -0,0 +1,156 @@
+use std::io::Cursor;
+
+use quick_xml::escape::escape;
+use serde::Deserialize;
+
+use crate::Result;
+
+/// The max number of objects in one `DeleteMultipleObjects` request.
+pub(crate) const MAX_DELETE_OBJECTS: usize = 1000;
+
+/// An object key with an optional version id.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectIdentifier {
+    pub key: String,
+    pub version_id: Option<String>,
+}
+
+impl ObjectIdentifier {
+    pub fn new(key: impl Into<String>) -> Self {
+        ObjectIdentifier {
+            key: key.into(),
+            version_id: None,
+        }
+    }
+
+    pub fn with_version(key: impl Into<String>, version_id: impl Into<String>) -> Self {
+        ObjectIdentifier {
+            key: key.into(),
+            version_id: Some(version_id.into()),
+        }
+    }
+}
+
+impl From<&str> for ObjectIdentifier {
+    fn from(value: &str) -> Self {
+        ObjectIdentifier::new(value)
+    }
+}
+
+impl From<String> for ObjectIdentifier {
+    fn from(value: String) -> Self {
+        ObjectIdentifier::new(value)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DeletedObject {
+    #[serde(rename = "Key")]
+    pub key: String,
+    #[serde(rename = "VersionId", default)]
+    pub version_id: Option<String>,
+    #[serde(rename = "DeleteMarker", default)]
+    pub delete_marker: bool,
+    #[serde(rename = "DeleteMarkerVersionId", default)]
+    pub delete_marker_version_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DeleteObjectError {
+    #[serde(rename = "Key")]
+    pub key: String,
+    #[serde(rename = "VersionId", default)]
+    pub version_id: Option<String>,
+    #[serde(rename = "Code", default)]
+    pub code: String,
+    #[serde(rename = "Message", default)]
+    pub message: String,
+}
+
+/// The result of `Bucket::delete_objects`, the deleted list is empty in quiet mode.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct DeleteObjectsResult {
+    #[serde(rename = "Deleted", default)]
+    pub deleted: Vec<DeletedObject>,
+    #[serde(rename = "Error", default)]
+    pub errors: Vec<DeleteObjectError>,
+}
+
+impl DeleteObjectsResult {
+    pub(crate) fn try_from_xml(xml: &[u8]) -> Result<Self> {
+        if xml.is_empty() {
+            return Ok(Default::default());
+        }
+        let c = Cursor::new(xml);
+        let res: DeleteObjectsResult = quick_xml::de::from_reader(c)?;
+        Ok(res)
+    }
+}
+
+pub(crate) fn build_delete_xml(objects: &[ObjectIdentifier], quiet: bool) -> String {
+    let mut xml = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    xml.push_str("<Delete>");
+    xml.push_str(&format!("<Quiet>{}</Quiet>", quiet));
+    for it in objects {
+        xml.push_str("<Object><Key>");
+        xml.push_str(&escape(&it.key));
+        xml.push_str("</Key>");
+        if let Some(v) = &it.version_id {
+            xml.push_str("<VersionId>");
+            xml.push_str(&escape(v));
+            xml.push_str("</VersionId>");
+        }
+        xml.push_str("</Object>");
+    }
+    xml.push_str("</Delete>");
+    xml
+}
+
+#[cfg(test)]
+mod test_super {
+    use super::*;
+
+    #[test]
+    fn test_build_delete_xml() {
+        let objects = vec![
+            ObjectIdentifier::new("a&b.txt"),
+            ObjectIdentifier::with_version("c.txt", "v1"),
+        ];
+        assert_eq!(
+            r#"<?xml version="1.0" encoding="UTF-8"?><Delete><Quiet>true</Quiet><Object><Key>a&amp;b.txt</Key></Object><Object><Key>c.txt</Key><VersionId>v1</VersionId></Object></Delete>"#,
+            build_delete_xml(&objects, true)
+        );
+    }
+
+    #[test]
+    fn test_delete_objects_result() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<DeleteResult>
+    <Deleted>
+        <Key>a.txt</Key>
+    </Deleted>
+    <Deleted>
+        <Key>b.txt</Key>
+        <DeleteMarker>true</DeleteMarker>
+        <DeleteMarkerVersionId>CAEQ</DeleteMarkerVersionId>
+    </Deleted>
+    <Error>
+        <Key>c.txt</Key>
+        <Code>AccessDenied</Code>
+        <Message>Access Denied</Message>
+    </Error>
+</DeleteResult>"#;
+        let res = DeleteObjectsResult::try_from_xml(xml.as_bytes()).unwrap();
+        assert_eq!(2, res.deleted.len());
+        assert!(res.deleted[1].delete_marker);
+        assert_eq!(
+            Some("CAEQ".to_string()),
+            res.deleted[1].delete_marker_version_id
+        );
+        assert_eq!(1, res.errors.len());
+        assert_eq!("AccessDenied", res.errors[0].code);
+
+        let res = DeleteObjectsResult::try_from_xml(b"").unwrap();
+        assert!(res.deleted.is_empty());
+    }
+}
@@ -0,0 +1,376 @@
+use std::ffi::OsString;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+
+use crate::bucket::Bucket;
+use crate::checkpoint::{self, Checkpoint};
+use crate::crc64;
+use crate::error::OSSError;
+use crate::object::{GetObjectOptions, ObjectMeta};
+use crate::Result;
+
+const MIN_PART_SIZE: u64 = 100 * 1024;
+const DEFAULT_PART_SIZE: u64 = 8 * 1024 * 1024;
+const DEFAULT_PARALLEL: usize = 3;
+
+/// Options of `Bucket::download_file`.
+#[derive(Debug, Clone)]
+pub struct DownloadFileOptions {
+    part_size: u64,
+    parallel: usize,
+    checkpoint_dir: Option<PathBuf>,
+}
+
+impl Default for DownloadFileOptions {
+    fn default() -> Self {
+        DownloadFileOptions {
+            part_size: DEFAULT_PART_SIZE,
+            parallel: DEFAULT_PARALLEL,
+            checkpoint_dir: None,
+        }
+    }
+}
+
+impl DownloadFileOptions {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// The size of each range, at least 100KB.
+    pub fn part_size(mut self, part_size: u64) -> Self {
+        self.part_size = part_size.max(MIN_PART_SIZE);
+        self
+    }
+
+    /// The number of ranges downloaded at the same time.
+    pub fn parallel(mut self, parallel: usize) -> Self {
+        self.parallel = parallel.max(1);
+        self
+    }
+
+    /// Enables the checkpoint, which is saved in the dir after each range is downloaded.
+    /// A failed download can be resumed by calling `download_file` again with the same dir.
+    pub fn checkpoint_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.checkpoint_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+}
+
+/// The remote object stat, the download restarts if it is changed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct ObjectStat {
+    size: u64,
+    etag: String,
+    last_modified: Option<i64>,
+}
+
+impl From<&ObjectMeta> for ObjectStat {
+    fn from(value: &ObjectMeta) -> Self {
+        ObjectStat {
+            size: value.content_length,
+            etag: value.etag.clone(),
+            last_modified: value.last_modified.map(|it| it.timestamp()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DownloadPart {
+    index: u32,
+    offset: u64,
+    size: u64,
+    completed: bool,
+    crc64: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct DownloadCheckpoint {
+    md5: String,
+    file: PathBuf,
+    bucket: String,
+    object: String,
+    object_stat: ObjectStat,
+    parts: Vec<DownloadPart>,
+}
+
+impl Checkpoint for DownloadCheckpoint {
+    fn md5_mut(&mut self) -> &mut String {
+        &mut self.md5
+    }
+}
+
+impl DownloadCheckpoint {
+    fn is_valid(&self, file: &Path, bucket: &str, object: &str, stat: &ObjectStat) -> bool {
+        self.file == file
+            && self.bucket == bucket
+            && self.object == object
+            && &self.object_stat == stat
+    }
+}
+
+fn split_parts(size: u64, part_size: u64) -> Vec<DownloadPart> {
+    let mut parts = vec![];
+    let mut offset = 0;
+    while offset < size {
+        let n = part_size.min(size - offset);
+        parts.push(DownloadPart {
+            index: parts.len() as u32,
+            offset,
+            size: n,
+            completed: false,
+            crc64: 0,
+        });
+        offset += n;
+    }
+    parts
+}
+
+fn temp_path(file: &Path) -> PathBuf {
+    let mut s = OsString::from(file.as_os_str());
+    s.push(".temp");
+    PathBuf::from(s)
+}
+
+async fn download_part(
+    bucket: &Bucket,
+    object: &str,
+    etag: &str,
+    temp: &Path,
+    part: &DownloadPart,
+) -> Result<u64> {
+    // 'If-Match' makes sure all the ranges come from the same version of the object
+    let options = GetObjectOptions::new()
+        .range(part.offset, part.offset + part.size - 1)
+        .if_match(etag);
+    let mut body = bucket
+        .get_object_stream_with_options(object, options)
+        .await?
+        .into_stream();
+
+    let mut f = tokio::fs::OpenOptions::new().write(true).open(temp).await?;
+    f.seek(SeekFrom::Start(part.offset)).await?;
+
+    let mut written = 0u64;
+    let mut crc = 0;
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk?;
+        written += chunk.len() as u64;
+        crc = crc64::update(crc, &chunk);
+        if written > part.size {
+            bail!("range of part {} returns too many bytes", part.index);
+        }
+        f.write_all(&chunk).await?;
+    }
+    if written != part.size {
+        bail!(
+            "range of part {} is incomplete: expect {} bytes, got {}",
+            part.index,
+            part.size,
+            written
+        );
+    }
+    f.sync_data().await?;
+    Ok(crc)
+}
+
+pub(crate) async fn download_file(
+    bucket: &Bucket,
+    object: &str,
+    file: &Path,
+    options: DownloadFileOptions,
+) -> Result<ObjectMeta> {
+    let meta = bucket.head_object(object).await?;
+    let stat = ObjectStat::from(&meta);
+    let temp = temp_path(file);
+    let cp_path = options
+        .checkpoint_dir
+        .as_ref()
+        .map(|dir| checkpoint::checkpoint_path(dir, file, bucket.name(), object));
+
+    let mut cp = None;
+    if let Some(path) = &cp_path {
+        if let Some(it) = checkpoint::load::<DownloadCheckpoint>(path).await? {
+            let temp_exists = tokio::fs::metadata(&temp).await.is_ok();
+            if temp_exists && it.is_valid(file, bucket.name(), object, &stat) {
+                info!("resume download from checkpoint {}", path.display());
+                cp = Some(it);
+            } else {
+                // the object is changed or the temp file is lost, start over
+                info!("discard outdated checkpoint {}", path.display());
+            }
+        }
+    }
+
+    let mut cp = match cp {
+        Some(it) => it,
+        None => {
+            // preallocate the temp file, so that the ranges can be written at any offset
+            let f = tokio::fs::File::create(&temp).await?;
+            f.set_len(stat.size).await?;
+            DownloadCheckpoint {
+                md5: Default::default(),
+                file: file.to_path_buf(),
+                bucket: bucket.name().into(),
+                object: object.into(),
+                object_stat: stat.clone(),
+                parts: split_parts(stat.size, options.part_size),
+            }
+        }
+    };
+
+    if let Some(path) = &cp_path {
+        checkpoint::save(path, &mut cp).await?;
+    }
+
+    let pending: Vec<DownloadPart> = cp
+        .parts
+        .iter()
+        .filter(|it| !it.completed)
+        .cloned()
+        .collect();
+
+    let mut downloads = futures::stream::iter(pending)
+        .map(|part| {
+            let etag = &stat.etag;
+            let temp = &temp;
+            async move {
+                let crc = download_part(bucket, object, etag, temp, &part).await?;
+                Ok::<_, anyhow::Error>((part.index, crc))
+            }
+        })
+        .buffer_unordered(options.parallel);
+
+    let mut failed = None;
+    while let Some(res) = downloads.next().await {
+        let (index, crc) = match res {
+            Ok(it) => it,
+            Err(e) => {
+                failed = Some(e);
+                break;
+            }
+        };
+        if let Some(part) = cp.parts.iter_mut().find(|it| it.index == index) {
+            part.completed = true;
+            part.crc64 = crc;
+        }
+        if let Some(path) = &cp_path {
+            checkpoint::save(path, &mut cp).await?;
+        }
+    }
+    drop(downloads);
+
+    if let Some(e) = failed {
+        // nothing will resume the download without a checkpoint
+        if cp_path.is_none() {
+            tokio::fs::remove_file(&temp).await.ok();
+        }
+        return Err(e);
+    }
+
+    if let Some(expect) = meta.crc64 {
+        let actual = cp
+            .parts
+            .iter()
+            .fold(0, |acc, it| crc64::combine(acc, it.crc64, it.size));
+        if actual != expect {
+            tokio::fs::remove_file(&temp).await.ok();
+            if let Some(path) = &cp_path {
+                checkpoint::remove(path).await?;
+            }
+            return Err(OSSError::CrcMismatch(actual, expect).into());
+        }
+    }
+
+    tokio::fs::rename(&temp, file).await?;
+
+    if let Some(path) = &cp_path {
+        checkpoint::remove(path).await?;
+    }
+
+    Ok(meta)
+}
+
+#[cfg(test)]
+mod test_super {
+    use super::*;
+
+    #[test]
+    fn test_split_parts() {
+        let parts = split_parts(250, 100);
+        assert_eq!(3, parts.len());
+        assert_eq!(
+            (2, 200, 50),
+            (parts[2].index, parts[2].offset, parts[2].size)
+        );
+        assert!(split_parts(0, 100).is_empty());
+    }
+
+    #[test]
+    fn test_combine_parts() {
+        let data = b"hello world, this is yunoss!";
+        let mut parts = split_parts(data.len() as u64, 10);
+        for it in parts.iter_mut() {
+            let (start, end) = (it.offset as usize, (it.offset + it.size) as usize);
+            it.crc64 = crc64::checksum(&data[start..end]);
+        }
+        let crc = parts
+            .iter()
+            .fold(0, |acc, it| crc64::combine(acc, it.crc64, it.size));
+        assert_eq!(crc64::checksum(data), crc);
+    }
+
+    #[test]
+    fn test_temp_path() {
+        assert_eq!(
+            PathBuf::from("/tmp/a.txt.temp"),
+            temp_path(Path::new("/tmp/a.txt"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_crc64() {
+        let data = "hello world";
+        let serve = |crc: u64| {
+            crate::test_support::serve_with_headers(move |req| {
+                let headers = vec![
+                    ("etag".to_string(), "\"etag\"".to_string()),
+                    ("x-oss-hash-crc64ecma".to_string(), crc.to_string()),
+                ];
+                match req.method.as_str() {
+                    "HEAD" => {
+                        let mut headers = headers;
+                        headers.push(("content-length".into(), data.len().to_string()));
+                        (200, headers, "".into())
+                    }
+                    _ => (206, headers, data.into()),
+                }
+            })
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("a.txt");
+
+        // the crc64 of the file is checked even if the crc of the requests is not enabled
+        let (endpoint, _) = serve(crc64::checksum(data.as_bytes()) + 1).await;
+        let bucket = crate::test_support::bucket(&endpoint);
+        let e = download_file(&bucket, "a.txt", &file, Default::default())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            e.downcast_ref::<OSSError>(),
+            Some(OSSError::CrcMismatch(..))
+        ));
+        assert!(!file.exists());
+        assert!(!temp_path(&file).exists());
+
+        let (endpoint, _) = serve(crc64::checksum(data.as_bytes())).await;
+        let bucket = crate::test_support::bucket(&endpoint);
+        download_file(&bucket, "a.txt", &file, Default::default())
+            .await
+            .unwrap();
+        assert_eq!(data, std::fs::read_to_string(&file).unwrap());
+    }
+}
@@ -32,6 +32,12 @@ pub enum OSSError {
     //oss: service returned error: StatusCode=%d, ErrorCode=%s, ErrorMessage=\"%s\", RequestId=%s
     #[error("oss: service returned error: StatusCode={0}, ErrorCode={1}, ErrorMessage='{2}', RequestId={3}")]
     ServiceError(u16, String, String, String),
+    #[error("oss: object not modified")]
+    NotModified,
+    #[error("oss: precondition failed, RequestId={0}")]
+    PreconditionFailed(String),
+    #[error("oss: the crc64 of client and server is inconsistent: ClientCRC={0}, ServerCRC={1}")]
+    CrcMismatch(u64, u64),
     #[error("{0}")]
     WithDescription(String),
 }
@@ -17,15 +17,62 @@ extern crate anyhow;
 
 pub type Result<T> = anyhow::Result<T>;
 
+mod append;
+mod body;
 mod bucket;
+mod checkpoint;
 mod client;
 mod config;
 mod conn;
+mod copy;
+mod crc64;
+mod credentials;
+mod delete;
+mod download;
 mod error;
+mod list;
+mod multipart;
+mod object;
+mod post;
+mod presign;
+mod retry;
+/// A minimal HTTP server which stands in for OSS, the metadata service or STS in the tests.
+#[cfg(test)]
+mod test_support;
+mod timeout;
 mod types;
+mod upload;
 mod util;
 mod version;
 
+pub use append::{AppendObjectOptions, AppendObjectResult, AppendWriter};
+pub use body::Body;
 pub use bucket::Bucket;
-pub use client::Client;
+pub use client::{Client, ClientBuilder};
+pub use config::{AuthVersion, HttpMaxConns, HttpTimeout};
+pub use copy::{CopyDirective, CopyObjectOptions, CopyObjectResult};
+pub use credentials::{
+    AssumeRoleCredentials, Credentials, CredentialsProvider, CredentialsSource,
+    DefaultCredentialsChain, EcsRamRoleCredentials, EnvCredentials, OidcCredentials,
+    ProfileCredentials, StaticCredentials,
+};
+pub use delete::{DeleteObjectError, DeleteObjectsResult, DeletedObject, ObjectIdentifier};
+pub use download::DownloadFileOptions;
+pub use error::OSSError;
+pub use list::{
+    ListEntry, ListObjectsV2Options, ListObjectsV2Result, ListStream, ObjectSummary, Owner,
+};
+pub use multipart::{
+    CompleteMultipartUploadOptions, CompleteMultipartUploadResult, InitiateMultipartUploadOptions,
+    ListMultipartUploadsOptions, ListMultipartUploadsResult, ListPartsOptions, ListPartsResult,
+    MultipartUpload, Part, PartInfo, UploadId,
+};
+pub use object::{
+    ByteStream, GetObjectOptions, GetObjectOutput, ObjectACL, ObjectMeta, ObjectType,
+    PutObjectOptions, PutObjectResult, StorageClass,
+};
+pub use post::{PostForm, PostPolicy};
+pub use presign::PresignOptions;
+pub use retry::{is_retryable_error, ExponentialBackoff, RetryAttempts, RetryPolicy};
+pub use upload::UploadFileOptions;
 pub use version::VERSION;
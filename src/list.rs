@@ -0,0 +1,334 @@
+use std::io::Cursor;
+use std::pin::Pin;
+
+use chrono::{DateTime, Utc};
+use futures::Stream;
+use serde::Deserialize;
+
+use crate::object::{ObjectType, StorageClass};
+use crate::types::Params;
+use crate::util;
+use crate::Result;
+
+/// Options of `Bucket::list_objects_v2` and `Bucket::list_all`.
+#[derive(Debug, Clone)]
+pub struct ListObjectsV2Options {
+    prefix: Option<String>,
+    delimiter: Option<String>,
+    start_after: Option<String>,
+    continuation_token: Option<String>,
+    max_keys: Option<u32>,
+    fetch_owner: bool,
+    url_encoding: bool,
+}
+
+impl Default for ListObjectsV2Options {
+    fn default() -> Self {
+        ListObjectsV2Options {
+            prefix: None,
+            delimiter: None,
+            start_after: None,
+            continuation_token: None,
+            max_keys: None,
+            fetch_owner: false,
+            url_encoding: true,
+        }
+    }
+}
+
+impl ListObjectsV2Options {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = Some(prefix.into());
+        self
+    }
+
+    /// Groups the keys which contain the delimiter after the prefix into common prefixes.
+    pub fn delimiter(mut self, delimiter: impl Into<String>) -> Self {
+        self.delimiter = Some(delimiter.into());
+        self
+    }
+
+    pub fn start_after(mut self, start_after: impl Into<String>) -> Self {
+        self.start_after = Some(start_after.into());
+        self
+    }
+
+    pub fn continuation_token(mut self, token: impl Into<String>) -> Self {
+        self.continuation_token = Some(token.into());
+        self
+    }
+
+    /// The max number of entries in one page, between [1-1000].
+    pub fn max_keys(mut self, max_keys: u32) -> Self {
+        self.max_keys = Some(max_keys);
+        self
+    }
+
+    pub fn fetch_owner(mut self, fetch_owner: bool) -> Self {
+        self.fetch_owner = fetch_owner;
+        self
+    }
+
+    /// Asks OSS to url-encode the keys in response (enabled by default), they are decoded
+    /// transparently. It is required for keys which contain characters invalid in XML.
+    pub fn url_encoding(mut self, enabled: bool) -> Self {
+        self.url_encoding = enabled;
+        self
+    }
+
+    pub(crate) fn to_params(&self) -> Params {
+        let mut params = Params::new();
+        params.insert("list-type".into(), Some("2".into()));
+        if let Some(it) = &self.prefix {
+            params.insert("prefix".into(), Some(it.clone()));
+        }
+        if let Some(it) = &self.delimiter {
+            params.insert("delimiter".into(), Some(it.clone()));
+        }
+        if let Some(it) = &self.start_after {
+            params.insert("start-after".into(), Some(it.clone()));
+        }
+        if let Some(it) = &self.continuation_token {
+            params.insert("continuation-token".into(), Some(it.clone()));
+        }
+        if let Some(it) = self.max_keys {
+            params.insert("max-keys".into(), Some(it.to_string()));
+        }
+        if self.fetch_owner {
+            params.insert("fetch-owner".into(), Some("true".into()));
+        }
+        if self.url_encoding {
+            params.insert("encoding-type".into(), Some("url".into()));
+        }
+        params
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Owner {
+    pub id: String,
+    pub display_name: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ObjectSummary {
+    pub key: String,
+    pub last_modified: Option<DateTime<Utc>>,
+    pub etag: String,
+    pub size: u64,
+    pub object_type: Option<ObjectType>,
+    pub storage_class: Option<StorageClass>,
+    pub owner: Option<Owner>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ListObjectsV2Result {
+    pub name: String,
+    pub prefix: String,
+    pub delimiter: String,
+    pub start_after: String,
+    pub max_keys: u32,
+    pub key_count: u32,
+    pub is_truncated: bool,
+    pub continuation_token: Option<String>,
+    pub next_continuation_token: Option<String>,
+    pub contents: Vec<ObjectSummary>,
+    pub common_prefixes: Vec<String>,
+}
+
+impl ListObjectsV2Result {
+    pub(crate) fn try_from_xml(xml: &[u8]) -> Result<Self> {
+        let c = Cursor::new(xml);
+        let raw: RawListObjectsV2Result = quick_xml::de::from_reader(c)?;
+
+        let url_encoded = raw.encoding_type.as_deref() == Some("url");
+        let decode = |s: String| -> Result<String> {
+            if url_encoded {
+                util::url_decode(&s)
+            } else {
+                Ok(s)
+            }
+        };
+
+        let mut contents = Vec::with_capacity(raw.contents.len());
+        for it in raw.contents {
+            contents.push(ObjectSummary {
+                key: decode(it.key)?,
+                last_modified: DateTime::parse_from_rfc3339(&it.last_modified)
+                    .ok()
+                    .map(|it| it.with_timezone(&Utc)),
+                etag: it.etag,
+                size: it.size,
+                object_type: ObjectType::parse(&it.object_type),
+                storage_class: StorageClass::parse(&it.storage_class),
+                owner: it.owner.map(|it| Owner {
+                    id: it.id,
+                    display_name: it.display_name,
+                }),
+            });
+        }
+
+        let mut common_prefixes = Vec::with_capacity(raw.common_prefixes.len());
+        for it in raw.common_prefixes {
+            common_prefixes.push(decode(it.prefix)?);
+        }
+
+        Ok(ListObjectsV2Result {
+            name: raw.name,
+            prefix: decode(raw.prefix)?,
+            delimiter: decode(raw.delimiter)?,
+            start_after: decode(raw.start_after)?,
+            max_keys: raw.max_keys,
+            key_count: raw.key_count,
+            is_truncated: raw.is_truncated,
+            continuation_token: raw.continuation_token,
+            next_continuation_token: raw.next_continuation_token,
+            contents,
+            common_prefixes,
+        })
+    }
+}
+
+/// An entry yielded by `Bucket::list_all`.
+#[derive(Debug, Clone)]
+pub enum ListEntry {
+    Object(ObjectSummary),
+    CommonPrefix(String),
+}
+
+/// A stream of all the entries, which follows the continuation token automatically.
+pub type ListStream = Pin<Box<dyn Stream<Item = Result<ListEntry>> + Send>>;
+
+#[derive(Debug, Deserialize)]
+struct RawOwner {
+    #[serde(rename = "ID", default)]
+    id: String,
+    #[serde(rename = "DisplayName", default)]
+    display_name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawObjectSummary {
+    #[serde(rename = "Key")]
+    key: String,
+    #[serde(rename = "LastModified", default)]
+    last_modified: String,
+    #[serde(rename = "ETag", default)]
+    etag: String,
+    #[serde(rename = "Type", default)]
+    object_type: String,
+    #[serde(rename = "Size", default)]
+    size: u64,
+    #[serde(rename = "StorageClass", default)]
+    storage_class: String,
+    #[serde(rename = "Owner", default)]
+    owner: Option<RawOwner>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawCommonPrefix {
+    #[serde(rename = "Prefix")]
+    prefix: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawListObjectsV2Result {
+    #[serde(rename = "Name", default)]
+    name: String,
+    #[serde(rename = "Prefix", default)]
+    prefix: String,
+    #[serde(rename = "Delimiter", default)]
+    delimiter: String,
+    #[serde(rename = "StartAfter", default)]
+    start_after: String,
+    #[serde(rename = "MaxKeys", default)]
+    max_keys: u32,
+    #[serde(rename = "KeyCount", default)]
+    key_count: u32,
+    #[serde(rename = "EncodingType", default)]
+    encoding_type: Option<String>,
+    #[serde(rename = "IsTruncated", default)]
+    is_truncated: bool,
+    #[serde(rename = "ContinuationToken", default)]
+    continuation_token: Option<String>,
+    #[serde(rename = "NextContinuationToken", default)]
+    next_continuation_token: Option<String>,
+    #[serde(rename = "Contents", default)]
+    contents: Vec<RawObjectSummary>,
+    #[serde(rename = "CommonPrefixes", default)]
+    common_prefixes: Vec<RawCommonPrefix>,
+}
+
+#[cfg(test)]
+mod test_super {
+    use super::*;
+
+    #[test]
+    fn test_list_objects_v2_result() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ListBucketResult>
+    <Name>examplebucket</Name>
+    <Prefix>a%2F</Prefix>
+    <MaxKeys>100</MaxKeys>
+    <EncodingType>url</EncodingType>
+    <Delimiter>%2F</Delimiter>
+    <IsTruncated>true</IsTruncated>
+    <NextContinuationToken>CgJiYw--</NextContinuationToken>
+    <Contents>
+        <Key>a%2Fhello+world.txt</Key>
+        <LastModified>2020-06-22T11:42:32.000Z</LastModified>
+        <ETag>"5B3C1A2E053D763E1B002CC607C5A0FE1****"</ETag>
+        <Type>Normal</Type>
+        <Size>344606</Size>
+        <StorageClass>Standard</StorageClass>
+        <Owner>
+            <ID>0022012****</ID>
+            <DisplayName>user-example</DisplayName>
+        </Owner>
+    </Contents>
+    <CommonPrefixes>
+        <Prefix>a%2Fsub%2F</Prefix>
+    </CommonPrefixes>
+    <KeyCount>2</KeyCount>
+</ListBucketResult>"#;
+
+        let res = ListObjectsV2Result::try_from_xml(xml.as_bytes()).unwrap();
+        assert_eq!("examplebucket", res.name);
+        assert_eq!("a/", res.prefix);
+        assert_eq!("/", res.delimiter);
+        assert!(res.is_truncated);
+        assert_eq!(Some("CgJiYw--".to_string()), res.next_continuation_token);
+        assert_eq!(1, res.contents.len());
+
+        let obj = &res.contents[0];
+        assert_eq!("a/hello world.txt", obj.key);
+        assert_eq!(344606, obj.size);
+        assert!(obj.last_modified.is_some());
+        assert_eq!(Some(ObjectType::Normal), obj.object_type);
+        assert_eq!(Some(StorageClass::Standard), obj.storage_class);
+        assert_eq!(
+            Some("user-example"),
+            obj.owner.as_ref().map(|it| &it.display_name[..])
+        );
+
+        assert_eq!(vec!["a/sub/".to_string()], res.common_prefixes);
+    }
+
+    #[test]
+    fn test_list_objects_v2_options() {
+        let params = ListObjectsV2Options::new()
+            .prefix("a/")
+            .delimiter("/")
+            .max_keys(10)
+            .to_params();
+        assert_eq!(Some(&Some("2".to_string())), params.get("list-type"));
+        assert_eq!(Some(&Some("a/".to_string())), params.get("prefix"));
+        assert_eq!(Some(&Some("10".to_string())), params.get("max-keys"));
+        assert_eq!(Some(&Some("url".to_string())), params.get("encoding-type"));
+        assert!(!params.contains_key("fetch-owner"));
+    }
+}
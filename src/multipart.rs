@@ -0,0 +1,588 @@
+use std::fmt::{Display, Formatter};
+use std::io::Cursor;
+
+use chrono::{DateTime, Utc};
+use quick_xml::escape::escape;
+use reqwest::header::HeaderMap;
+use serde::Deserialize;
+
+use crate::error::OSSError;
+use crate::object::{header_str, PutObjectOptions};
+use crate::types::{
+    Headers, Params, HEADER_ETAG, HEADER_OSS_HASH_CRC64, HEADER_OSS_REQUEST_ID,
+    HEADER_OSS_VERSION_ID,
+};
+use crate::util;
+use crate::Result;
+
+/// The max number of parts of a multipart upload.
+pub(crate) const MAX_PART_NUMBER: u32 = 10000;
+const CODE_NO_SUCH_UPLOAD: &str = "NoSuchUpload";
+
+/// Returns true if the upload is completed, aborted or expired.
+pub(crate) fn is_no_such_upload(e: &anyhow::Error) -> bool {
+    matches!(
+        e.downcast_ref::<OSSError>(),
+        Some(OSSError::ServiceError(_, code, _, _)) if code == CODE_NO_SUCH_UPLOAD
+    )
+}
+
+/// The id of a multipart upload, returned by `Bucket::initiate_multipart_upload`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UploadId(String);
+
+impl UploadId {
+    pub fn new(id: impl Into<String>) -> Self {
+        UploadId(id.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Display for UploadId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Options of `Bucket::initiate_multipart_upload_with_options`.
+#[derive(Debug, Clone, Default)]
+pub struct InitiateMultipartUploadOptions {
+    object: PutObjectOptions,
+    sequential: bool,
+}
+
+impl InitiateMultipartUploadOptions {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets the content type, user metadata, storage class, ACL... of the final object.
+    pub fn object_options(mut self, options: PutObjectOptions) -> Self {
+        self.object = options;
+        self
+    }
+
+    /// Requires the parts to be uploaded in order, then the ETag of the object is the MD5 of its content.
+    pub fn sequential(mut self, sequential: bool) -> Self {
+        self.sequential = sequential;
+        self
+    }
+
+    pub(crate) fn into_parts(self) -> (Params, Headers) {
+        let mut params = Params::new();
+        params.insert("uploads".into(), None);
+        if self.sequential {
+            params.insert("sequential".into(), None);
+        }
+        (params, self.object.into_headers())
+    }
+}
+
+impl From<PutObjectOptions> for InitiateMultipartUploadOptions {
+    fn from(value: PutObjectOptions) -> Self {
+        InitiateMultipartUploadOptions::new().object_options(value)
+    }
+}
+
+/// Options of `Bucket::complete_multipart_upload_with_options`.
+#[derive(Debug, Clone, Default)]
+pub struct CompleteMultipartUploadOptions {
+    retry: bool,
+}
+
+impl CompleteMultipartUploadOptions {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Retries the request on transient failures like the other requests, it is disabled by
+    /// default since the upload may have been completed before the failure, then the retry
+    /// fails with `NoSuchUpload`.
+    pub fn retry(mut self, retry: bool) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub(crate) fn retry_enabled(&self) -> bool {
+        self.retry
+    }
+}
+
+/// An uploaded part, which is required to complete the multipart upload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartInfo {
+    pub part_number: u32,
+    pub etag: String,
+    pub crc64: Option<u64>,
+}
+
+impl PartInfo {
+    pub(crate) fn from_headers(part_number: u32, headers: &HeaderMap) -> Self {
+        PartInfo {
+            part_number,
+            etag: header_str(headers, HEADER_ETAG).unwrap_or_default(),
+            crc64: header_str(headers, HEADER_OSS_HASH_CRC64).and_then(|it| it.parse().ok()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CompleteMultipartUploadResult {
+    pub location: String,
+    pub bucket: String,
+    pub key: String,
+    pub etag: String,
+    pub crc64: Option<u64>,
+    pub version_id: Option<String>,
+    pub request_id: String,
+}
+
+impl CompleteMultipartUploadResult {
+    pub(crate) fn try_from_response(headers: &HeaderMap, xml: &[u8]) -> Result<Self> {
+        let c = Cursor::new(xml);
+        let raw: RawCompleteMultipartUploadResult = quick_xml::de::from_reader(c)?;
+        let url_encoded = raw.encoding_type.as_deref() == Some("url");
+        Ok(CompleteMultipartUploadResult {
+            location: raw.location,
+            bucket: raw.bucket,
+            key: if url_encoded {
+                util::url_decode(&raw.key)?
+            } else {
+                raw.key
+            },
+            etag: raw.etag,
+            crc64: header_str(headers, HEADER_OSS_HASH_CRC64).and_then(|it| it.parse().ok()),
+            version_id: header_str(headers, HEADER_OSS_VERSION_ID),
+            request_id: header_str(headers, HEADER_OSS_REQUEST_ID).unwrap_or_default(),
+        })
+    }
+}
+
+/// Options of `Bucket::list_parts`.
+#[derive(Debug, Clone, Default)]
+pub struct ListPartsOptions {
+    max_parts: Option<u32>,
+    part_number_marker: Option<u32>,
+}
+
+impl ListPartsOptions {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn max_parts(mut self, max_parts: u32) -> Self {
+        self.max_parts = Some(max_parts);
+        self
+    }
+
+    /// Lists the parts after this part number.
+    pub fn part_number_marker(mut self, marker: u32) -> Self {
+        self.part_number_marker = Some(marker);
+        self
+    }
+
+    pub(crate) fn into_params(self, upload_id: &UploadId) -> Params {
+        let mut params = Params::new();
+        params.insert("uploadId".into(), Some(upload_id.to_string()));
+        if let Some(it) = self.max_parts {
+            params.insert("max-parts".into(), Some(it.to_string()));
+        }
+        if let Some(it) = self.part_number_marker {
+            params.insert("part-number-marker".into(), Some(it.to_string()));
+        }
+        params
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Part {
+    #[serde(rename = "PartNumber")]
+    pub part_number: u32,
+    #[serde(rename = "LastModified", default)]
+    pub last_modified: String,
+    #[serde(rename = "ETag", default)]
+    pub etag: String,
+    #[serde(rename = "Size", default)]
+    pub size: u64,
+    #[serde(rename = "HashCrc64ecma", default)]
+    pub crc64: Option<u64>,
+}
+
+impl From<Part> for PartInfo {
+    fn from(value: Part) -> Self {
+        PartInfo {
+            part_number: value.part_number,
+            etag: value.etag,
+            crc64: value.crc64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ListPartsResult {
+    #[serde(rename = "Bucket", default)]
+    pub bucket: String,
+    #[serde(rename = "Key", default)]
+    pub key: String,
+    #[serde(rename = "UploadId", default)]
+    pub upload_id: String,
+    #[serde(rename = "PartNumberMarker", default)]
+    pub part_number_marker: u32,
+    #[serde(rename = "NextPartNumberMarker", default)]
+    pub next_part_number_marker: u32,
+    #[serde(rename = "MaxParts", default)]
+    pub max_parts: u32,
+    #[serde(rename = "IsTruncated", default)]
+    pub is_truncated: bool,
+    #[serde(rename = "Part", default)]
+    pub parts: Vec<Part>,
+}
+
+impl ListPartsResult {
+    pub(crate) fn try_from_xml(xml: &[u8]) -> Result<Self> {
+        let c = Cursor::new(xml);
+        let res: ListPartsResult = quick_xml::de::from_reader(c)?;
+        Ok(res)
+    }
+}
+
+/// Options of `Bucket::list_multipart_uploads`.
+#[derive(Debug, Clone, Default)]
+pub struct ListMultipartUploadsOptions {
+    prefix: Option<String>,
+    delimiter: Option<String>,
+    key_marker: Option<String>,
+    upload_id_marker: Option<String>,
+    max_uploads: Option<u32>,
+}
+
+impl ListMultipartUploadsOptions {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = Some(prefix.into());
+        self
+    }
+
+    pub fn delimiter(mut self, delimiter: impl Into<String>) -> Self {
+        self.delimiter = Some(delimiter.into());
+        self
+    }
+
+    pub fn key_marker(mut self, key_marker: impl Into<String>) -> Self {
+        self.key_marker = Some(key_marker.into());
+        self
+    }
+
+    pub fn upload_id_marker(mut self, upload_id_marker: impl Into<String>) -> Self {
+        self.upload_id_marker = Some(upload_id_marker.into());
+        self
+    }
+
+    pub fn max_uploads(mut self, max_uploads: u32) -> Self {
+        self.max_uploads = Some(max_uploads);
+        self
+    }
+
+    pub(crate) fn into_params(self) -> Params {
+        let mut params = Params::new();
+        params.insert("uploads".into(), None);
+        params.insert("encoding-type".into(), Some("url".into()));
+        if let Some(it) = self.prefix {
+            params.insert("prefix".into(), Some(it));
+        }
+        if let Some(it) = self.delimiter {
+            params.insert("delimiter".into(), Some(it));
+        }
+        if let Some(it) = self.key_marker {
+            params.insert("key-marker".into(), Some(it));
+        }
+        if let Some(it) = self.upload_id_marker {
+            params.insert("upload-id-marker".into(), Some(it));
+        }
+        if let Some(it) = self.max_uploads {
+            params.insert("max-uploads".into(), Some(it.to_string()));
+        }
+        params
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MultipartUpload {
+    pub key: String,
+    pub upload_id: UploadId,
+    pub initiated: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ListMultipartUploadsResult {
+    pub bucket: String,
+    pub prefix: String,
+    pub delimiter: String,
+    pub key_marker: String,
+    pub upload_id_marker: String,
+    pub next_key_marker: String,
+    pub next_upload_id_marker: String,
+    pub max_uploads: u32,
+    pub is_truncated: bool,
+    pub uploads: Vec<MultipartUpload>,
+    pub common_prefixes: Vec<String>,
+}
+
+impl ListMultipartUploadsResult {
+    pub(crate) fn try_from_xml(xml: &[u8]) -> Result<Self> {
+        let c = Cursor::new(xml);
+        let raw: RawListMultipartUploadsResult = quick_xml::de::from_reader(c)?;
+
+        let url_encoded = raw.encoding_type.as_deref() == Some("url");
+        let decode = |s: String| -> Result<String> {
+            if url_encoded {
+                util::url_decode(&s)
+            } else {
+                Ok(s)
+            }
+        };
+
+        let mut uploads = Vec::with_capacity(raw.uploads.len());
+        for it in raw.uploads {
+            uploads.push(MultipartUpload {
+                key: decode(it.key)?,
+                upload_id: UploadId(it.upload_id),
+                initiated: DateTime::parse_from_rfc3339(&it.initiated)
+                    .ok()
+                    .map(|it| it.with_timezone(&Utc)),
+            });
+        }
+
+        let mut common_prefixes = Vec::with_capacity(raw.common_prefixes.len());
+        for it in raw.common_prefixes {
+            common_prefixes.push(decode(it.prefix)?);
+        }
+
+        Ok(ListMultipartUploadsResult {
+            bucket: raw.bucket,
+            prefix: decode(raw.prefix)?,
+            delimiter: decode(raw.delimiter)?,
+            key_marker: decode(raw.key_marker)?,
+            upload_id_marker: raw.upload_id_marker,
+            next_key_marker: decode(raw.next_key_marker)?,
+            next_upload_id_marker: raw.next_upload_id_marker,
+            max_uploads: raw.max_uploads,
+            is_truncated: raw.is_truncated,
+            uploads,
+            common_prefixes,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct InitiateMultipartUploadResult {
+    #[serde(rename = "UploadId")]
+    pub(crate) upload_id: String,
+}
+
+impl InitiateMultipartUploadResult {
+    pub(crate) fn try_from_xml(xml: &[u8]) -> Result<Self> {
+        let c = Cursor::new(xml);
+        let res: InitiateMultipartUploadResult = quick_xml::de::from_reader(c)?;
+        Ok(res)
+    }
+}
+
+pub(crate) fn build_complete_xml(parts: &[PartInfo]) -> String {
+    let mut parts: Vec<&PartInfo> = parts.iter().collect();
+    parts.sort_by_key(|it| it.part_number);
+
+    let mut xml = String::from("<CompleteMultipartUpload>");
+    for it in parts {
+        xml.push_str(&format!(
+            "<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>",
+            it.part_number,
+            escape(&it.etag)
+        ));
+    }
+    xml.push_str("</CompleteMultipartUpload>");
+    xml
+}
+
+pub(crate) fn check_part_number(part_number: u32) -> Result<()> {
+    if !(1..=MAX_PART_NUMBER).contains(&part_number) {
+        bail!(
+            "part number {} is out of range [1-{}]",
+            part_number,
+            MAX_PART_NUMBER
+        );
+    }
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+struct RawCompleteMultipartUploadResult {
+    #[serde(rename = "EncodingType", default)]
+    encoding_type: Option<String>,
+    #[serde(rename = "Location", default)]
+    location: String,
+    #[serde(rename = "Bucket", default)]
+    bucket: String,
+    #[serde(rename = "Key", default)]
+    key: String,
+    #[serde(rename = "ETag", default)]
+    etag: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawMultipartUpload {
+    #[serde(rename = "Key")]
+    key: String,
+    #[serde(rename = "UploadId")]
+    upload_id: String,
+    #[serde(rename = "Initiated", default)]
+    initiated: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawCommonPrefix {
+    #[serde(rename = "Prefix")]
+    prefix: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawListMultipartUploadsResult {
+    #[serde(rename = "Bucket", default)]
+    bucket: String,
+    #[serde(rename = "EncodingType", default)]
+    encoding_type: Option<String>,
+    #[serde(rename = "Prefix", default)]
+    prefix: String,
+    #[serde(rename = "Delimiter", default)]
+    delimiter: String,
+    #[serde(rename = "KeyMarker", default)]
+    key_marker: String,
+    #[serde(rename = "UploadIdMarker", default)]
+    upload_id_marker: String,
+    #[serde(rename = "NextKeyMarker", default)]
+    next_key_marker: String,
+    #[serde(rename = "NextUploadIdMarker", default)]
+    next_upload_id_marker: String,
+    #[serde(rename = "MaxUploads", default)]
+    max_uploads: u32,
+    #[serde(rename = "IsTruncated", default)]
+    is_truncated: bool,
+    #[serde(rename = "Upload", default)]
+    uploads: Vec<RawMultipartUpload>,
+    #[serde(rename = "CommonPrefixes", default)]
+    common_prefixes: Vec<RawCommonPrefix>,
+}
+
+#[cfg(test)]
+mod test_super {
+    use super::*;
+
+    #[test]
+    fn test_build_complete_xml() {
+        let parts = vec![
+            PartInfo {
+                part_number: 2,
+                etag: "\"B\"".into(),
+                crc64: None,
+            },
+            PartInfo {
+                part_number: 1,
+                etag: "\"A\"".into(),
+                crc64: None,
+            },
+        ];
+        assert_eq!(
+            "<CompleteMultipartUpload><Part><PartNumber>1</PartNumber><ETag>&quot;A&quot;</ETag></Part><Part><PartNumber>2</PartNumber><ETag>&quot;B&quot;</ETag></Part></CompleteMultipartUpload>",
+            build_complete_xml(&parts)
+        );
+    }
+
+    #[test]
+    fn test_initiate_result() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<InitiateMultipartUploadResult>
+    <Bucket>oss-example</Bucket>
+    <Key>multipart.data</Key>
+    <UploadId>0004B9894A22E5B1888A1E29F823****</UploadId>
+</InitiateMultipartUploadResult>"#;
+        let res = InitiateMultipartUploadResult::try_from_xml(xml.as_bytes()).unwrap();
+        assert_eq!("0004B9894A22E5B1888A1E29F823****", res.upload_id);
+    }
+
+    #[test]
+    fn test_list_parts_result() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ListPartsResult>
+    <Bucket>multipart_upload</Bucket>
+    <Key>multipart.data</Key>
+    <UploadId>0004B999EF5A239BB9138C6227D6****</UploadId>
+    <NextPartNumberMarker>5</NextPartNumberMarker>
+    <MaxParts>1000</MaxParts>
+    <IsTruncated>false</IsTruncated>
+    <Part>
+        <PartNumber>1</PartNumber>
+        <LastModified>2012-02-23T07:01:34.000Z</LastModified>
+        <ETag>"3349DC700140D7F86A0784842780****"</ETag>
+        <HashCrc64ecma>12345</HashCrc64ecma>
+        <Size>6291456</Size>
+    </Part>
+    <Part>
+        <PartNumber>2</PartNumber>
+        <LastModified>2012-02-23T07:01:12.000Z</LastModified>
+        <ETag>"3349DC700140D7F86A0784842780****"</ETag>
+        <Size>6291456</Size>
+    </Part>
+</ListPartsResult>"#;
+        let res = ListPartsResult::try_from_xml(xml.as_bytes()).unwrap();
+        assert_eq!(5, res.next_part_number_marker);
+        assert!(!res.is_truncated);
+        assert_eq!(2, res.parts.len());
+        assert_eq!(Some(12345), res.parts[0].crc64);
+        assert_eq!(None, res.parts[1].crc64);
+        assert_eq!(6291456, res.parts[1].size);
+    }
+
+    #[test]
+    fn test_list_multipart_uploads_result() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ListMultipartUploadsResult>
+    <Bucket>oss-example</Bucket>
+    <EncodingType>url</EncodingType>
+    <KeyMarker></KeyMarker>
+    <UploadIdMarker></UploadIdMarker>
+    <NextKeyMarker>oss.avi</NextKeyMarker>
+    <NextUploadIdMarker>0004B99B8E707874FC2D692FA5D7****</NextUploadIdMarker>
+    <Delimiter></Delimiter>
+    <Prefix></Prefix>
+    <MaxUploads>1000</MaxUploads>
+    <IsTruncated>false</IsTruncated>
+    <Upload>
+        <Key>a%2Fmultipart.data</Key>
+        <UploadId>0004B999EF518A1FE585B0C9360D****</UploadId>
+        <Initiated>2012-02-23T04:18:23.000Z</Initiated>
+    </Upload>
+</ListMultipartUploadsResult>"#;
+        let res = ListMultipartUploadsResult::try_from_xml(xml.as_bytes()).unwrap();
+        assert_eq!(1, res.uploads.len());
+        assert_eq!("a/multipart.data", res.uploads[0].key);
+        assert_eq!(
+            "0004B999EF518A1FE585B0C9360D****",
+            res.uploads[0].upload_id.as_str()
+        );
+        assert!(res.uploads[0].initiated.is_some());
+    }
+
+    #[test]
+    fn test_check_part_number() {
+        assert!(check_part_number(0).is_err());
+        assert!(check_part_number(1).is_ok());
+        assert!(check_part_number(MAX_PART_NUMBER).is_ok());
+        assert!(check_part_number(MAX_PART_NUMBER + 1).is_err());
+    }
+}
@@ -0,0 +1,706 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures::{Stream, StreamExt, TryStreamExt};
+use reqwest::header::HeaderMap;
+use tokio::io::AsyncRead;
+use tokio_util::io::StreamReader;
+
+use crate::crc64;
+use crate::error::OSSError;
+use crate::timeout::stall_timeout;
+use crate::types::{
+    Headers, Params, HEADER_ETAG, HEADER_OSS_HASH_CRC64, HEADER_OSS_META_PREFIX,
+    HEADER_OSS_REQUEST_ID, HEADER_OSS_TAGGING, HEADER_OSS_VERSION_ID,
+};
+use crate::util;
+use crate::Result;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageClass {
+    Standard,
+    IA,
+    Archive,
+    ColdArchive,
+    DeepColdArchive,
+}
+
+impl StorageClass {
+    pub(crate) fn parse(s: &str) -> Option<StorageClass> {
+        match s {
+            "Standard" => Some(StorageClass::Standard),
+            "IA" => Some(StorageClass::IA),
+            "Archive" => Some(StorageClass::Archive),
+            "ColdArchive" => Some(StorageClass::ColdArchive),
+            "DeepColdArchive" => Some(StorageClass::DeepColdArchive),
+            _ => None,
+        }
+    }
+}
+
+impl Display for StorageClass {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            StorageClass::Standard => "Standard",
+            StorageClass::IA => "IA",
+            StorageClass::Archive => "Archive",
+            StorageClass::ColdArchive => "ColdArchive",
+            StorageClass::DeepColdArchive => "DeepColdArchive",
+        };
+        f.write_str(s)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectACL {
+    Default,
+    Private,
+    PublicRead,
+    PublicReadWrite,
+}
+
+impl Display for ObjectACL {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            ObjectACL::Default => "default",
+            ObjectACL::Private => "private",
+            ObjectACL::PublicRead => "public-read",
+            ObjectACL::PublicReadWrite => "public-read-write",
+        };
+        f.write_str(s)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectType {
+    Normal,
+    Appendable,
+    Multipart,
+    Symlink,
+}
+
+impl ObjectType {
+    pub(crate) fn parse(s: &str) -> Option<ObjectType> {
+        match s {
+            "Normal" => Some(ObjectType::Normal),
+            "Appendable" => Some(ObjectType::Appendable),
+            "Multipart" => Some(ObjectType::Multipart),
+            "Symlink" => Some(ObjectType::Symlink),
+            _ => None,
+        }
+    }
+}
+
+/// The restore status of an archived object, parsed from the `x-oss-restore` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RestoreStatus {
+    Ongoing,
+    Restored { expiry_date: Option<DateTime<Utc>> },
+}
+
+impl RestoreStatus {
+    pub(crate) fn parse(s: &str) -> Option<RestoreStatus> {
+        // ongoing-request="false", expiry-date="Sun, 16 Apr 2017 08:12:33 GMT"
+        let quoted = |key: &str| -> Option<&str> {
+            let start = s.find(key)? + key.len();
+            let rest = s[start..].trim_start().strip_prefix('=')?.trim_start();
+            let rest = rest.strip_prefix('"')?;
+            rest.find('"').map(|end| &rest[..end])
+        };
+        let ongoing = quoted("ongoing-request").map(|it| it == "true");
+        let expiry_date = quoted("expiry-date").and_then(util::parse_httptime);
+        match ongoing? {
+            true => Some(RestoreStatus::Ongoing),
+            false => Some(RestoreStatus::Restored { expiry_date }),
+        }
+    }
+}
+
+/// The metadata of an object, returned by `Bucket::head_object` and `Bucket::get_object_meta`.
+///
+/// `get_object_meta` only returns the basic fields, the others will be empty.
+#[derive(Debug, Clone, Default)]
+pub struct ObjectMeta {
+    pub content_length: u64,
+    pub etag: String,
+    pub last_modified: Option<DateTime<Utc>>,
+    pub content_type: Option<String>,
+    pub storage_class: Option<StorageClass>,
+    pub object_type: Option<ObjectType>,
+    pub crc64: Option<u64>,
+    pub version_id: Option<String>,
+    pub server_side_encryption: Option<String>,
+    pub server_side_encryption_key_id: Option<String>,
+    pub restore: Option<RestoreStatus>,
+    pub metadata: BTreeMap<String, String>,
+    pub request_id: String,
+}
+
+impl ObjectMeta {
+    pub(crate) fn from_headers(headers: &HeaderMap) -> Self {
+        let mut metadata = BTreeMap::new();
+        for (k, v) in headers {
+            if let Some(key) = k.as_str().strip_prefix(HEADER_OSS_META_PREFIX) {
+                if let Ok(v) = v.to_str() {
+                    metadata.insert(key.to_string(), v.to_string());
+                }
+            }
+        }
+
+        ObjectMeta {
+            content_length: header_str(headers, "content-length")
+                .and_then(|it| it.parse().ok())
+                .unwrap_or_default(),
+            etag: header_str(headers, HEADER_ETAG).unwrap_or_default(),
+            last_modified: header_str(headers, "last-modified")
+                .and_then(|it| util::parse_httptime(&it)),
+            content_type: header_str(headers, "content-type"),
+            storage_class: header_str(headers, "x-oss-storage-class")
+                .and_then(|it| StorageClass::parse(&it)),
+            object_type: header_str(headers, "x-oss-object-type")
+                .and_then(|it| ObjectType::parse(&it)),
+            crc64: header_str(headers, HEADER_OSS_HASH_CRC64).and_then(|it| it.parse().ok()),
+            version_id: header_str(headers, HEADER_OSS_VERSION_ID),
+            server_side_encryption: header_str(headers, "x-oss-server-side-encryption"),
+            server_side_encryption_key_id: header_str(
+                headers,
+                "x-oss-server-side-encryption-key-id",
+            ),
+            restore: header_str(headers, "x-oss-restore").and_then(|it| RestoreStatus::parse(&it)),
+            metadata,
+            request_id: header_str(headers, HEADER_OSS_REQUEST_ID).unwrap_or_default(),
+        }
+    }
+}
+
+/// Options of `Bucket::put_object_with_options`.
+#[derive(Debug, Clone, Default)]
+pub struct PutObjectOptions {
+    content_type: Option<String>,
+    cache_control: Option<String>,
+    content_disposition: Option<String>,
+    storage_class: Option<StorageClass>,
+    acl: Option<ObjectACL>,
+    forbid_overwrite: bool,
+    metadata: BTreeMap<String, String>,
+    tagging: BTreeMap<String, String>,
+    timeout: Option<Duration>,
+}
+
+impl PutObjectOptions {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn content_type(mut self, content_type: impl Into<String>) -> Self {
+        self.content_type = Some(content_type.into());
+        self
+    }
+
+    pub fn cache_control(mut self, cache_control: impl Into<String>) -> Self {
+        self.cache_control = Some(cache_control.into());
+        self
+    }
+
+    pub fn content_disposition(mut self, content_disposition: impl Into<String>) -> Self {
+        self.content_disposition = Some(content_disposition.into());
+        self
+    }
+
+    pub fn storage_class(mut self, storage_class: StorageClass) -> Self {
+        self.storage_class = Some(storage_class);
+        self
+    }
+
+    pub fn acl(mut self, acl: ObjectACL) -> Self {
+        self.acl = Some(acl);
+        self
+    }
+
+    /// Fails the upload with `FileAlreadyExists` if an object with the same key exists.
+    pub fn forbid_overwrite(mut self, forbid: bool) -> Self {
+        self.forbid_overwrite = forbid;
+        self
+    }
+
+    /// Adds an user metadata, which will be sent as `x-oss-meta-<key>`.
+    pub fn meta(mut self, key: impl AsRef<str>, value: impl Into<String>) -> Self {
+        self.metadata
+            .insert(key.as_ref().to_lowercase(), value.into());
+        self
+    }
+
+    /// Adds a tag of the object, which will be sent in `x-oss-tagging`.
+    pub fn tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.tagging.insert(key.into(), value.into());
+        self
+    }
+
+    /// The timeout of the whole upload, which overrides `HttpTimeout::long`.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub(crate) fn timeout_override(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Takes the content type, cache control, content disposition and user metadata
+    /// from the headers of another object.
+    pub(crate) fn inherit(mut self, headers: &HeaderMap) -> Self {
+        let meta = ObjectMeta::from_headers(headers);
+        self.content_type = meta.content_type;
+        self.cache_control = header_str(headers, "cache-control");
+        self.content_disposition = header_str(headers, "content-disposition");
+        self.metadata = meta.metadata;
+        self
+    }
+
+    pub(crate) fn into_headers(self) -> Headers {
+        let mut headers = Headers::new();
+        if let Some(it) = self.content_type {
+            headers.insert("content-type".into(), it);
+        }
+        if let Some(it) = self.cache_control {
+            headers.insert("cache-control".into(), it);
+        }
+        if let Some(it) = self.content_disposition {
+            headers.insert("content-disposition".into(), it);
+        }
+        if let Some(it) = self.storage_class {
+            headers.insert("x-oss-storage-class".into(), it.to_string());
+        }
+        if let Some(it) = self.acl {
+            headers.insert("x-oss-object-acl".into(), it.to_string());
+        }
+        if self.forbid_overwrite {
+            headers.insert("x-oss-forbid-overwrite".into(), "true".into());
+        }
+        for (k, v) in self.metadata {
+            headers.insert(format!("{}{}", HEADER_OSS_META_PREFIX, k), v);
+        }
+        if !self.tagging.is_empty() {
+            headers.insert(HEADER_OSS_TAGGING.into(), util::tagging_str(&self.tagging));
+        }
+        headers
+    }
+}
+
+/// Options of `Bucket::get_object_with_options`.
+///
+/// A conditional request which is not satisfied fails with `OSSError::NotModified`
+/// or `OSSError::PreconditionFailed`.
+#[derive(Debug, Clone, Default)]
+pub struct GetObjectOptions {
+    range: Option<(u64, Option<u64>)>,
+    if_match: Option<String>,
+    if_none_match: Option<String>,
+    if_modified_since: Option<DateTime<Utc>>,
+    if_unmodified_since: Option<DateTime<Utc>>,
+    response_headers: BTreeMap<&'static str, String>,
+    timeout: Option<Duration>,
+}
+
+impl GetObjectOptions {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Reads the bytes between `start` and `end`, both inclusive.
+    pub fn range(mut self, start: u64, end: u64) -> Self {
+        self.range = Some((start, Some(end)));
+        self
+    }
+
+    /// Reads the bytes from `start` to the end of the object.
+    pub fn range_from(mut self, start: u64) -> Self {
+        self.range = Some((start, None));
+        self
+    }
+
+    pub fn if_match(mut self, etag: impl Into<String>) -> Self {
+        self.if_match = Some(etag.into());
+        self
+    }
+
+    pub fn if_none_match(mut self, etag: impl Into<String>) -> Self {
+        self.if_none_match = Some(etag.into());
+        self
+    }
+
+    pub fn if_modified_since(mut self, t: DateTime<Utc>) -> Self {
+        self.if_modified_since = Some(t);
+        self
+    }
+
+    pub fn if_unmodified_since(mut self, t: DateTime<Utc>) -> Self {
+        self.if_unmodified_since = Some(t);
+        self
+    }
+
+    pub fn response_content_type(self, value: impl Into<String>) -> Self {
+        self.response_header("response-content-type", value)
+    }
+
+    pub fn response_content_language(self, value: impl Into<String>) -> Self {
+        self.response_header("response-content-language", value)
+    }
+
+    pub fn response_expires(self, value: impl Into<String>) -> Self {
+        self.response_header("response-expires", value)
+    }
+
+    pub fn response_cache_control(self, value: impl Into<String>) -> Self {
+        self.response_header("response-cache-control", value)
+    }
+
+    pub fn response_content_disposition(self, value: impl Into<String>) -> Self {
+        self.response_header("response-content-disposition", value)
+    }
+
+    pub fn response_content_encoding(self, value: impl Into<String>) -> Self {
+        self.response_header("response-content-encoding", value)
+    }
+
+    /// The timeout of the whole download including reading the body, which overrides
+    /// `HttpTimeout::long`.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    fn response_header(mut self, key: &'static str, value: impl Into<String>) -> Self {
+        self.response_headers.insert(key, value.into());
+        self
+    }
+
+    pub(crate) fn timeout_override(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    pub(crate) fn into_parts(self) -> (Option<Params>, Headers) {
+        let mut headers = Headers::new();
+        if let Some((start, end)) = self.range {
+            let range = match end {
+                Some(end) => format!("bytes={}-{}", start, end),
+                None => format!("bytes={}-", start),
+            };
+            headers.insert("range".into(), range);
+            headers.insert("x-oss-range-behavior".into(), "standard".into());
+        }
+        if let Some(it) = self.if_match {
+            headers.insert("if-match".into(), it);
+        }
+        if let Some(it) = self.if_none_match {
+            headers.insert("if-none-match".into(), it);
+        }
+        if let Some(it) = self.if_modified_since {
+            headers.insert("if-modified-since".into(), util::format_httptime(&it));
+        }
+        if let Some(it) = self.if_unmodified_since {
+            headers.insert("if-unmodified-since".into(), util::format_httptime(&it));
+        }
+
+        if self.response_headers.is_empty() {
+            return (None, headers);
+        }
+
+        let params = self
+            .response_headers
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), Some(v)))
+            .collect();
+        (Some(params), headers)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PutObjectResult {
+    pub etag: String,
+    pub crc64: Option<u64>,
+    pub version_id: Option<String>,
+    pub request_id: String,
+}
+
+impl PutObjectResult {
+    pub(crate) fn from_headers(headers: &HeaderMap) -> Self {
+        PutObjectResult {
+            etag: header_str(headers, HEADER_ETAG).unwrap_or_default(),
+            crc64: header_str(headers, HEADER_OSS_HASH_CRC64).and_then(|it| it.parse().ok()),
+            version_id: header_str(headers, HEADER_OSS_VERSION_ID),
+            request_id: header_str(headers, HEADER_OSS_REQUEST_ID).unwrap_or_default(),
+        }
+    }
+}
+
+/// A stream of the object content chunks.
+pub type ByteStream = Pin<Box<dyn Stream<Item = Result<Bytes>> + Send>>;
+
+/// The response of `Bucket::get_object_stream`, the body is not read until it is consumed.
+pub struct GetObjectOutput {
+    headers: HeaderMap,
+    body: ByteStream,
+}
+
+impl GetObjectOutput {
+    /// Creates the output, the crc64 of the body is verified at the end of the stream if
+    /// `check_crc` is true and the whole object is returned. Reading the body fails if no
+    /// bytes are received within `stall`.
+    pub(crate) fn new(resp: reqwest::Response, check_crc: bool, stall: Duration) -> Self {
+        let headers = resp.headers().clone();
+        let whole = resp.status() == reqwest::StatusCode::OK;
+        let body = stall_timeout(resp.bytes_stream().map_err(|e| e.into()), stall);
+
+        let server_crc = header_str(&headers, HEADER_OSS_HASH_CRC64).and_then(|it| it.parse().ok());
+        let body = match server_crc {
+            Some(expect) if check_crc && whole => Crc64Stream {
+                inner: body,
+                crc: 0,
+                expect,
+                done: false,
+            }
+            .boxed(),
+            _ => body,
+        };
+
+        GetObjectOutput { headers, body }
+    }
+
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    pub fn etag(&self) -> Option<String> {
+        header_str(&self.headers, HEADER_ETAG)
+    }
+
+    pub fn content_length(&self) -> Option<u64> {
+        header_str(&self.headers, "content-length").and_then(|it| it.parse().ok())
+    }
+
+    pub fn content_type(&self) -> Option<String> {
+        header_str(&self.headers, "content-type")
+    }
+
+    pub fn crc64(&self) -> Option<u64> {
+        header_str(&self.headers, HEADER_OSS_HASH_CRC64).and_then(|it| it.parse().ok())
+    }
+
+    pub fn request_id(&self) -> Option<String> {
+        header_str(&self.headers, HEADER_OSS_REQUEST_ID)
+    }
+
+    pub fn into_stream(self) -> ByteStream {
+        self.body
+    }
+
+    pub fn into_async_read(self) -> impl AsyncRead + Send + Unpin {
+        let stream = self
+            .body
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e));
+        StreamReader::new(stream)
+    }
+
+    /// Reads the whole body into memory.
+    pub async fn bytes(self) -> Result<Bytes> {
+        let mut body = self.body;
+        let mut b = Vec::with_capacity(
+            header_str(&self.headers, "content-length")
+                .and_then(|it| it.parse().ok())
+                .unwrap_or_default(),
+        );
+        while let Some(chunk) = body.next().await {
+            b.extend_from_slice(&chunk?);
+        }
+        Ok(b.into())
+    }
+}
+
+/// A stream which accumulates the crc64 of the chunks, and fails at the end if it is
+/// different from the server one.
+struct Crc64Stream {
+    inner: ByteStream,
+    crc: u64,
+    expect: u64,
+    done: bool,
+}
+
+impl Stream for Crc64Stream {
+    type Item = Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+        match ready!(self.inner.as_mut().poll_next(cx)) {
+            Some(Ok(b)) => {
+                self.crc = crc64::update(self.crc, &b);
+                Poll::Ready(Some(Ok(b)))
+            }
+            Some(Err(e)) => {
+                self.done = true;
+                Poll::Ready(Some(Err(e)))
+            }
+            None => {
+                self.done = true;
+                if self.crc != self.expect {
+                    let e = OSSError::CrcMismatch(self.crc, self.expect);
+                    return Poll::Ready(Some(Err(e.into())));
+                }
+                Poll::Ready(None)
+            }
+        }
+    }
+}
+
+#[inline]
+pub(crate) fn header_str(headers: &HeaderMap, key: &str) -> Option<String> {
+    headers
+        .get(key)
+        .and_then(|it| it.to_str().ok())
+        .map(|it| it.to_string())
+}
+
+#[cfg(test)]
+mod test_super {
+    use super::*;
+
+    #[test]
+    fn test_put_object_options() {
+        let headers = PutObjectOptions::new()
+            .content_type("text/plain")
+            .storage_class(StorageClass::IA)
+            .acl(ObjectACL::PublicRead)
+            .forbid_overwrite(true)
+            .meta("Author", "jeffsky")
+            .into_headers();
+
+        assert_eq!(
+            Some("text/plain"),
+            headers.get("content-type").map(|it| &it[..])
+        );
+        assert_eq!(
+            Some("IA"),
+            headers.get("x-oss-storage-class").map(|it| &it[..])
+        );
+        assert_eq!(
+            Some("public-read"),
+            headers.get("x-oss-object-acl").map(|it| &it[..])
+        );
+        assert_eq!(
+            Some("true"),
+            headers.get("x-oss-forbid-overwrite").map(|it| &it[..])
+        );
+        assert_eq!(
+            Some("jeffsky"),
+            headers.get("x-oss-meta-author").map(|it| &it[..])
+        );
+    }
+
+    #[test]
+    fn test_object_meta() {
+        let mut headers = HeaderMap::new();
+        headers.insert("content-length", "1024".parse().unwrap());
+        headers.insert("etag", "\"5B3C1A2E05\"".parse().unwrap());
+        headers.insert(
+            "last-modified",
+            "Fri, 24 Feb 2012 06:07:48 GMT".parse().unwrap(),
+        );
+        headers.insert("x-oss-object-type", "Appendable".parse().unwrap());
+        headers.insert("x-oss-storage-class", "Archive".parse().unwrap());
+        headers.insert("x-oss-hash-crc64ecma", "12345".parse().unwrap());
+        headers.insert("x-oss-meta-author", "jeffsky".parse().unwrap());
+        headers.insert(
+            "x-oss-restore",
+            "ongoing-request=\"false\", expiry-date=\"Sun, 16 Apr 2017 08:12:33 GMT\""
+                .parse()
+                .unwrap(),
+        );
+
+        let meta = ObjectMeta::from_headers(&headers);
+        assert_eq!(1024, meta.content_length);
+        assert_eq!("\"5B3C1A2E05\"", meta.etag);
+        assert!(meta.last_modified.is_some());
+        assert_eq!(Some(ObjectType::Appendable), meta.object_type);
+        assert_eq!(Some(StorageClass::Archive), meta.storage_class);
+        assert_eq!(Some(12345), meta.crc64);
+        assert_eq!(Some(&"jeffsky".to_string()), meta.metadata.get("author"));
+        assert!(matches!(
+            meta.restore,
+            Some(RestoreStatus::Restored {
+                expiry_date: Some(_)
+            })
+        ));
+        assert_eq!(
+            Some(RestoreStatus::Ongoing),
+            RestoreStatus::parse("ongoing-request=\"true\"")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_crc64_stream() {
+        let chunks = || {
+            futures::stream::iter(vec![
+                Ok(Bytes::from_static(b"hello ")),
+                Ok(Bytes::from_static(b"world")),
+            ])
+            .boxed()
+        };
+
+        let expect = crc64::checksum(b"hello world");
+        let stream = Crc64Stream {
+            inner: chunks(),
+            crc: 0,
+            expect,
+            done: false,
+        };
+        let res: Vec<Bytes> = stream.try_collect().await.unwrap();
+        assert_eq!(2, res.len());
+
+        let stream = Crc64Stream {
+            inner: chunks(),
+            crc: 0,
+            expect: expect + 1,
+            done: false,
+        };
+        let err = stream.try_collect::<Vec<Bytes>>().await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<OSSError>(),
+            Some(OSSError::CrcMismatch(_, _))
+        ));
+    }
+
+    #[test]
+    fn test_get_object_options() {
+        let (params, headers) = GetObjectOptions::new()
+            .range(0, 99)
+            .if_none_match("\"abc\"")
+            .response_content_type("text/plain")
+            .into_parts();
+
+        assert_eq!(Some("bytes=0-99"), headers.get("range").map(|it| &it[..]));
+        assert_eq!(
+            Some("standard"),
+            headers.get("x-oss-range-behavior").map(|it| &it[..])
+        );
+        assert_eq!(
+            Some("\"abc\""),
+            headers.get("if-none-match").map(|it| &it[..])
+        );
+        let params = params.unwrap();
+        assert_eq!(
+            Some(&Some("text/plain".to_string())),
+            params.get("response-content-type")
+        );
+
+        let (params, headers) = GetObjectOptions::new().range_from(100).into_parts();
+        assert!(params.is_none());
+        assert_eq!(Some("bytes=100-"), headers.get("range").map(|it| &it[..]));
+    }
+}
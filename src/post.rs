@@ -0,0 +1,208 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{json, Value};
+
+use crate::conn::Conn;
+use crate::Result;
+
+/// The policy of `PostObject`, which restricts what a browser can upload with the form
+/// returned by `Bucket::post_policy_form`.
+#[derive(Debug, Clone)]
+pub struct PostPolicy {
+    expires: Duration,
+    conditions: Vec<Value>,
+    fields: BTreeMap<String, String>,
+}
+
+impl PostPolicy {
+    /// Creates a policy which is valid for `expires`.
+    pub fn new(expires: Duration) -> Self {
+        PostPolicy {
+            expires,
+            conditions: vec![],
+            fields: BTreeMap::new(),
+        }
+    }
+
+    /// Uploads to the exact key, which is also filled in the form.
+    pub fn key(self, key: impl Into<String>) -> Self {
+        self.field("key", key)
+    }
+
+    /// Requires the key to start with the prefix, the `key` field should be filled by the browser.
+    pub fn key_starts_with(self, prefix: impl Into<String>) -> Self {
+        self.starts_with("key", prefix)
+    }
+
+    /// Limits the size of the uploaded file in bytes.
+    pub fn content_length_range(mut self, min: u64, max: u64) -> Self {
+        self.conditions
+            .push(json!(["content-length-range", min, max]));
+        self
+    }
+
+    /// The status code returned on success when there is no redirect, 200 (default), 201 or 204.
+    pub fn success_action_status(self, status: u16) -> Self {
+        self.field("success_action_status", status.to_string())
+    }
+
+    /// Asks OSS to call back the url after the upload, the body can reference the variables
+    /// like `bucket=${bucket}&object=${object}&size=${size}`.
+    pub fn callback(self, url: impl Into<String>, body: impl Into<String>) -> Self {
+        let callback = json!({
+            "callbackUrl": url.into(),
+            "callbackBody": body.into(),
+            "callbackBodyType": "application/x-www-form-urlencoded",
+        });
+        self.field("callback", STANDARD.encode(callback.to_string()))
+    }
+
+    /// Requires the field to be equal to the value, e.g. `eq("bucket", "examplebucket")`.
+    pub fn eq(mut self, field: impl AsRef<str>, value: impl Into<String>) -> Self {
+        let field = format!("${}", field.as_ref());
+        self.conditions.push(json!(["eq", field, value.into()]));
+        self
+    }
+
+    /// Requires the field to start with the prefix, e.g. `starts_with("content-type", "image/")`.
+    pub fn starts_with(mut self, field: impl AsRef<str>, prefix: impl Into<String>) -> Self {
+        let field = format!("${}", field.as_ref());
+        self.conditions
+            .push(json!(["starts-with", field, prefix.into()]));
+        self
+    }
+
+    /// Adds a field to the form, which is also required by the policy.
+    pub fn field(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.fields.insert(name.into(), value.into());
+        self
+    }
+
+    /// Encodes the policy and signs it, the credential fields of the signature are added
+    /// to both the conditions and the form.
+    pub(crate) async fn into_form(
+        mut self,
+        conn: &Conn,
+        bucket: &str,
+        now: DateTime<Utc>,
+    ) -> Result<PostForm> {
+        let cred = conn.credentials().await?;
+        for (k, v) in conn.post_credential_fields(&cred, &now)? {
+            self.fields.insert(k, v);
+        }
+
+        let mut conditions = vec![json!({ "bucket": bucket })];
+        for (k, v) in &self.fields {
+            conditions.push(json!({ k.as_str(): v }));
+        }
+        conditions.extend(self.conditions);
+
+        let expiration = now + chrono::Duration::from_std(self.expires)?;
+        let policy = json!({
+            "expiration": expiration.to_rfc3339_opts(SecondsFormat::Millis, true),
+            "conditions": conditions,
+        });
+        let policy = STANDARD.encode(policy.to_string());
+
+        let mut fields = self.fields;
+        for (k, v) in conn.sign_post_policy(&cred, &policy, &now)? {
+            fields.insert(k, v);
+        }
+        fields.insert("policy".into(), policy);
+        Ok(PostForm {
+            url: conn.get_url(bucket, ""),
+            fields,
+        })
+    }
+}
+
+/// The form of `PostObject`, the fields should be sent before the `file` field.
+#[derive(Debug, Clone)]
+pub struct PostForm {
+    /// The url of the bucket which the form is posted to.
+    pub url: String,
+    pub fields: BTreeMap<String, String>,
+}
+
+#[cfg(test)]
+mod test_super {
+    use std::sync::Arc;
+
+    use crate::config::{AuthVersion, ClientConfig};
+    use crate::conn::UrlMaker;
+
+    use super::*;
+
+    fn new_conn(auth_version: AuthVersion) -> Conn {
+        let mut config = ClientConfig::default();
+        config.access_key_id = "ak".into();
+        config.access_key_secret = "sk".into();
+        config.security_token = "token".into();
+        config.auth_version = auth_version;
+        config.region = "cn-hangzhou".into();
+        let um = UrlMaker::new("https://oss-cn-hangzhou.aliyuncs.com", false, false).unwrap();
+        Conn::new(Arc::new(config), Arc::new(um), reqwest::Client::new())
+    }
+
+    fn decode_policy(form: &PostForm) -> Value {
+        let b = STANDARD.decode(&form.fields["policy"]).unwrap();
+        serde_json::from_slice(&b).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_post_policy_v1() {
+        let now = DateTime::from_timestamp(1701605532, 0).unwrap();
+        let form = PostPolicy::new(Duration::from_secs(3600))
+            .key_starts_with("uploads/")
+            .content_length_range(1, 1024)
+            .success_action_status(201)
+            .into_form(&new_conn(AuthVersion::V1), "bucket", now)
+            .await
+            .unwrap();
+
+        assert_eq!("https://bucket.oss-cn-hangzhou.aliyuncs.com/", form.url);
+        assert_eq!("ak", form.fields["OSSAccessKeyId"]);
+        assert_eq!("token", form.fields["x-oss-security-token"]);
+        assert_eq!("201", form.fields["success_action_status"]);
+        assert!(!form.fields.contains_key("key"));
+
+        let policy = decode_policy(&form);
+        assert_eq!("2023-12-03T13:12:12.000Z", policy["expiration"]);
+        let conditions = policy["conditions"].as_array().unwrap();
+        assert!(conditions.contains(&json!({"bucket": "bucket"})));
+        assert!(conditions.contains(&json!(["starts-with", "$key", "uploads/"])));
+        assert!(conditions.contains(&json!(["content-length-range", 1, 1024])));
+        assert!(conditions.contains(&json!({"success_action_status": "201"})));
+    }
+
+    #[tokio::test]
+    async fn test_post_policy_v4() {
+        let now = DateTime::from_timestamp(1701605532, 0).unwrap();
+        let form = PostPolicy::new(Duration::from_secs(60))
+            .key("a.txt")
+            .into_form(&new_conn(AuthVersion::V4), "bucket", now)
+            .await
+            .unwrap();
+
+        assert_eq!("a.txt", form.fields["key"]);
+        assert_eq!("OSS4-HMAC-SHA256", form.fields["x-oss-signature-version"]);
+        assert_eq!(
+            "ak/20231203/cn-hangzhou/oss/aliyun_v4_request",
+            form.fields["x-oss-credential"]
+        );
+        // the signing key is derived from "aliyun_v4sk", "20231203", "cn-hangzhou", "oss" and
+        // "aliyun_v4_request", which signs the base64 encoded policy
+        assert_eq!(
+            "a018d3c822714801559a850931efc9c5186dc5d3542ad784051f08232de33cc2",
+            form.fields["x-oss-signature"]
+        );
+
+        let policy = decode_policy(&form);
+        let conditions = policy["conditions"].as_array().unwrap();
+        assert!(conditions.contains(&json!({"x-oss-date": "20231203T121212Z"})));
+        assert!(conditions.contains(&json!({"x-oss-security-token": "token"})));
+    }
+}
@@ -0,0 +1,141 @@
+use std::collections::BTreeMap;
+
+use crate::types::{Headers, Params};
+use crate::Result;
+
+/// The range of `x-oss-traffic-limit` in bit/s: 100KB/s ~ 100MB/s.
+const MIN_TRAFFIC_LIMIT: u64 = 100 * 1024 * 8;
+const MAX_TRAFFIC_LIMIT: u64 = 100 * 1024 * 1024 * 8;
+
+/// Options of `Bucket::presign_url`.
+///
+/// The headers (e.g. `Content-Type`) are signed, so the user of the URL must send them as is.
+#[derive(Debug, Clone, Default)]
+pub struct PresignOptions {
+    content_type: Option<String>,
+    content_md5: Option<String>,
+    traffic_limit: Option<u64>,
+    params: BTreeMap<&'static str, String>,
+}
+
+impl PresignOptions {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// The `Content-Type` of an upload URL.
+    pub fn content_type(mut self, content_type: impl Into<String>) -> Self {
+        self.content_type = Some(content_type.into());
+        self
+    }
+
+    /// The base64 encoded `Content-MD5` of an upload URL.
+    pub fn content_md5(mut self, content_md5: impl Into<String>) -> Self {
+        self.content_md5 = Some(content_md5.into());
+        self
+    }
+
+    /// Processes the image, video... before it is downloaded, e.g. `image/resize,w_100`.
+    pub fn process(self, process: impl Into<String>) -> Self {
+        self.param("x-oss-process", process)
+    }
+
+    /// Limits the speed of the transfer in bit/s, between 819200 (100KB/s) and
+    /// 838860800 (100MB/s), `presign_url` returns an error if it is out of the range.
+    pub fn traffic_limit(mut self, limit: u64) -> Self {
+        self.traffic_limit = Some(limit);
+        self
+    }
+
+    pub fn version_id(self, version_id: impl Into<String>) -> Self {
+        self.param("versionId", version_id)
+    }
+
+    pub fn response_content_type(self, value: impl Into<String>) -> Self {
+        self.param("response-content-type", value)
+    }
+
+    pub fn response_content_language(self, value: impl Into<String>) -> Self {
+        self.param("response-content-language", value)
+    }
+
+    pub fn response_expires(self, value: impl Into<String>) -> Self {
+        self.param("response-expires", value)
+    }
+
+    pub fn response_cache_control(self, value: impl Into<String>) -> Self {
+        self.param("response-cache-control", value)
+    }
+
+    pub fn response_content_disposition(self, value: impl Into<String>) -> Self {
+        self.param("response-content-disposition", value)
+    }
+
+    pub fn response_content_encoding(self, value: impl Into<String>) -> Self {
+        self.param("response-content-encoding", value)
+    }
+
+    fn param(mut self, key: &'static str, value: impl Into<String>) -> Self {
+        self.params.insert(key, value.into());
+        self
+    }
+
+    pub(crate) fn into_parts(mut self) -> Result<(Params, Headers)> {
+        if let Some(limit) = self.traffic_limit {
+            if !(MIN_TRAFFIC_LIMIT..=MAX_TRAFFIC_LIMIT).contains(&limit) {
+                bail!(
+                    "traffic limit {} is out of range [{}, {}]",
+                    limit,
+                    MIN_TRAFFIC_LIMIT,
+                    MAX_TRAFFIC_LIMIT
+                );
+            }
+            self.params.insert("x-oss-traffic-limit", limit.to_string());
+        }
+        let mut headers = Headers::new();
+        if let Some(it) = self.content_type {
+            headers.insert("content-type".into(), it);
+        }
+        if let Some(it) = self.content_md5 {
+            headers.insert("content-md5".into(), it);
+        }
+        let params = self
+            .params
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), Some(v)))
+            .collect();
+        Ok((params, headers))
+    }
+}
+
+#[cfg(test)]
+mod test_super {
+    use super::*;
+
+    #[test]
+    fn test_into_parts() {
+        let (params, headers) = PresignOptions::new()
+            .content_type("text/plain")
+            .traffic_limit(MIN_TRAFFIC_LIMIT)
+            .response_content_disposition("attachment")
+            .into_parts()
+            .unwrap();
+        assert_eq!("text/plain", headers["content-type"]);
+        assert_eq!(
+            Some(MIN_TRAFFIC_LIMIT.to_string()),
+            params["x-oss-traffic-limit"]
+        );
+        assert_eq!(
+            Some("attachment".to_string()),
+            params["response-content-disposition"]
+        );
+    }
+
+    #[test]
+    fn test_traffic_limit_out_of_range() {
+        let options = PresignOptions::new().traffic_limit(MIN_TRAFFIC_LIMIT - 1);
+        assert!(options.into_parts().is_err());
+        let options = PresignOptions::new().traffic_limit(MAX_TRAFFIC_LIMIT + 1);
+        assert!(options.into_parts().is_err());
+    }
+}
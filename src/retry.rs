@@ -0,0 +1,197 @@
+use std::fmt::{Debug, Display, Formatter};
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::error::OSSError;
+
+const DEFAULT_BASE_DELAY: Duration = Duration::from_millis(200);
+const DEFAULT_MAX_DELAY: Duration = Duration::from_secs(20);
+
+/// The error codes of OSS which are worth retrying.
+const RETRYABLE_CODES: [&str; 4] = [
+    "RequestTimeout",
+    "InternalError",
+    "ServiceUnavailable",
+    "SlowDown",
+];
+
+/// Decides whether and when a failed request is retried.
+///
+/// A request is only retried if its body can be replayed, i.e. it is not a reader which is
+/// neither buffered nor spooled by the MD5 computation.
+pub trait RetryPolicy: Send + Sync {
+    /// The max number of retries after the first attempt.
+    fn max_retries(&self) -> u32;
+
+    /// Returns true if the request failed with the error should be retried.
+    fn is_retryable(&self, error: &anyhow::Error) -> bool {
+        is_retryable_error(error)
+    }
+
+    /// The delay before the `retry`th retry, which starts from 1.
+    fn backoff(&self, retry: u32) -> Duration;
+}
+
+impl<T: RetryPolicy + ?Sized> RetryPolicy for Arc<T> {
+    fn max_retries(&self) -> u32 {
+        (**self).max_retries()
+    }
+
+    fn is_retryable(&self, error: &anyhow::Error) -> bool {
+        (**self).is_retryable(error)
+    }
+
+    fn backoff(&self, retry: u32) -> Duration {
+        (**self).backoff(retry)
+    }
+}
+
+impl Debug for dyn RetryPolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("max_retries", &self.max_retries())
+            .finish()
+    }
+}
+
+/// The default policy, which doubles the delay on each retry up to `max_delay`, with a random
+/// jitter of up to half of the delay.
+#[derive(Debug, Clone)]
+pub struct ExponentialBackoff {
+    max_retries: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+}
+
+impl ExponentialBackoff {
+    pub fn new(max_retries: u32) -> Self {
+        ExponentialBackoff {
+            max_retries,
+            base_delay: DEFAULT_BASE_DELAY,
+            max_delay: DEFAULT_MAX_DELAY,
+        }
+    }
+
+    /// The delay of the first retry, 200ms by default.
+    pub fn base_delay(mut self, delay: Duration) -> Self {
+        self.base_delay = delay;
+        self
+    }
+
+    /// The upper bound of the delay, 20s by default.
+    pub fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+}
+
+impl RetryPolicy for ExponentialBackoff {
+    fn max_retries(&self) -> u32 {
+        self.max_retries
+    }
+
+    fn backoff(&self, retry: u32) -> Duration {
+        let factor = 1u32 << retry.saturating_sub(1).min(16);
+        let delay = self.base_delay.saturating_mul(factor).min(self.max_delay);
+        let half = delay / 2;
+        let jitter = fastrand::u64(..=half.as_millis() as u64);
+        half + Duration::from_millis(jitter)
+    }
+}
+
+/// Returns true if the error is transient: a 5xx or 429 response, the error codes like
+/// `RequestTimeout` or `InternalError`, a timeout, or a broken connection.
+pub fn is_retryable_error(error: &anyhow::Error) -> bool {
+    if let Some(OSSError::ServiceError(status, code, _, _)) = error.downcast_ref::<OSSError>() {
+        return *status >= 500 || *status == 429 || RETRYABLE_CODES.contains(&code.as_str());
+    }
+    for cause in error.chain() {
+        if let Some(e) = cause.downcast_ref::<reqwest::Error>() {
+            if e.is_timeout() || e.is_connect() {
+                return true;
+            }
+        }
+        if let Some(e) = cause.downcast_ref::<io::Error>() {
+            if matches!(
+                e.kind(),
+                io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::BrokenPipe
+                    | io::ErrorKind::UnexpectedEof
+                    | io::ErrorKind::TimedOut
+            ) {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// The context of an error which is returned after retries, the number of attempts can be
+/// read by `error.downcast_ref::<RetryAttempts>()`.
+#[derive(Debug, Clone)]
+pub struct RetryAttempts {
+    attempts: u32,
+    message: String,
+}
+
+impl RetryAttempts {
+    pub(crate) fn new(attempts: u32, error: &anyhow::Error) -> Self {
+        RetryAttempts {
+            attempts,
+            message: error.to_string(),
+        }
+    }
+
+    /// The number of attempts including the first one.
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+}
+
+impl Display for RetryAttempts {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (after {} attempts)", self.message, self.attempts)
+    }
+}
+
+#[cfg(test)]
+mod test_super {
+    use super::*;
+
+    fn service_error(status: u16, code: &str) -> anyhow::Error {
+        OSSError::ServiceError(status, code.into(), "".into(), "".into()).into()
+    }
+
+    #[test]
+    fn test_is_retryable_error() {
+        assert!(is_retryable_error(&service_error(500, "InternalError")));
+        assert!(is_retryable_error(&service_error(503, "")));
+        assert!(is_retryable_error(&service_error(429, "")));
+        assert!(is_retryable_error(&service_error(400, "RequestTimeout")));
+        assert!(!is_retryable_error(&service_error(403, "AccessDenied")));
+        assert!(!is_retryable_error(&service_error(404, "NoSuchKey")));
+        assert!(!is_retryable_error(&OSSError::NotModified.into()));
+
+        let e = io::Error::new(io::ErrorKind::ConnectionReset, "reset");
+        assert!(is_retryable_error(&anyhow::Error::new(e).context("send")));
+        let e = io::Error::new(io::ErrorKind::NotFound, "no file");
+        assert!(!is_retryable_error(&e.into()));
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = ExponentialBackoff::new(3)
+            .base_delay(Duration::from_millis(100))
+            .max_delay(Duration::from_millis(1000));
+        for _ in 0..10 {
+            let d = policy.backoff(1);
+            assert!(d >= Duration::from_millis(50) && d <= Duration::from_millis(100));
+            let d = policy.backoff(3);
+            assert!(d >= Duration::from_millis(200) && d <= Duration::from_millis(400));
+            let d = policy.backoff(100);
+            assert!(d >= Duration::from_millis(500) && d <= Duration::from_millis(1000));
+        }
+    }
+}
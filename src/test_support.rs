@@ -0,0 +1,111 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+/// A request received by the server.
+#[derive(Debug, Clone)]
+pub(crate) struct Request {
+    pub(crate) method: String,
+    pub(crate) path: String,
+    pub(crate) headers: Vec<(String, String)>,
+    pub(crate) body: String,
+}
+
+impl Request {
+    pub(crate) fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Serves the requests with the handler, returns the base url and the received requests.
+pub(crate) async fn serve<F>(handler: F) -> (String, Arc<Mutex<Vec<Request>>>)
+where
+    F: Fn(&Request) -> (u16, String) + Send + Sync + 'static,
+{
+    serve_with_headers(move |req| {
+        let (status, body) = handler(req);
+        (status, vec![], body)
+    })
+    .await
+}
+
+/// Like `serve`, the handler also returns the headers of the response.
+pub(crate) async fn serve_with_headers<F>(handler: F) -> (String, Arc<Mutex<Vec<Request>>>)
+where
+    F: Fn(&Request) -> (u16, Vec<(String, String)>, String) + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(vec![]));
+    let received = requests.clone();
+    let handler = Arc::new(handler);
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let (handler, received) = (handler.clone(), received.clone());
+            tokio::spawn(async move {
+                let (r, mut w) = stream.into_split();
+                let mut r = BufReader::new(r);
+                let mut line = String::new();
+                r.read_line(&mut line).await.unwrap();
+                let mut parts = line.split_whitespace();
+                let method = parts.next().unwrap_or_default().to_string();
+                let path = parts.next().unwrap_or_default().to_string();
+                let mut headers = vec![];
+                loop {
+                    line.clear();
+                    r.read_line(&mut line).await.unwrap();
+                    match line.trim_end().split_once(':') {
+                        Some((k, v)) => headers.push((k.to_string(), v.trim().to_string())),
+                        None => break,
+                    }
+                }
+                let len = headers
+                    .iter()
+                    .find(|(k, _)| k.eq_ignore_ascii_case("content-length"))
+                    .and_then(|(_, v)| v.parse().ok())
+                    .unwrap_or(0);
+                let mut body = vec![0; len];
+                r.read_exact(&mut body).await.unwrap();
+                let req = Request {
+                    method,
+                    path,
+                    headers,
+                    body: String::from_utf8(body).unwrap(),
+                };
+                let (status, headers, body) = handler(&req);
+                received.lock().unwrap().push(req);
+                // the content length of a HEAD response is the one of the object
+                let mut head = format!("HTTP/1.1 {} X\r\nconnection: close\r\n", status);
+                if !headers.iter().any(|(k, _)| k == "content-length") {
+                    head.push_str(&format!("content-length: {}\r\n", body.len()));
+                }
+                for (k, v) in &headers {
+                    head.push_str(&format!("{}: {}\r\n", k, v));
+                }
+                let res = format!("{}\r\n{}", head, body);
+                w.write_all(res.as_bytes()).await.unwrap();
+            });
+        }
+    });
+    (base_url, requests)
+}
+
+/// A bucket which sends the requests to the server, the retries do not wait.
+pub(crate) fn bucket(endpoint: &str) -> crate::Bucket {
+    let policy = crate::retry::ExponentialBackoff::new(2).base_delay(Duration::from_millis(1));
+    crate::Client::builder()
+        .endpoint(endpoint)
+        .cname(true)
+        .access_key("ak")
+        .access_secret("sk")
+        .retry_policy(policy)
+        .build()
+        .unwrap()
+        .bucket("bucket")
+        .unwrap()
+}
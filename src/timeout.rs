@@ -0,0 +1,187 @@
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::future::{self, Either};
+use futures::stream::{self, BoxStream};
+use futures::{Stream, StreamExt};
+
+use crate::Result;
+
+/// Tracks the progress of sending a request, which is watched by `watch_stall`.
+#[derive(Debug, Clone)]
+pub(crate) struct Progress {
+    last: Arc<Mutex<Instant>>,
+    sending: Arc<AtomicBool>,
+}
+
+impl Progress {
+    /// Creates the progress of a request, `sending` is false if there is no body to send.
+    pub(crate) fn new(sending: bool) -> Self {
+        Progress {
+            last: Arc::new(Mutex::new(Instant::now())),
+            sending: Arc::new(AtomicBool::new(sending)),
+        }
+    }
+
+    /// Marks that some bytes of the body are sent.
+    pub(crate) fn touch(&self) {
+        *self.last.lock().unwrap() = Instant::now();
+    }
+
+    /// Whether the body is still being sent.
+    pub(crate) fn is_sending(&self) -> bool {
+        self.sending.load(Ordering::SeqCst)
+    }
+
+    /// Marks that the whole body is sent, the response headers are waited since then.
+    pub(crate) fn finish(&self) {
+        self.touch();
+        self.sending.store(false, Ordering::SeqCst);
+    }
+
+    /// Wraps the body stream to track the sent chunks.
+    pub(crate) fn wrap<S>(&self, stream: S) -> BoxStream<'static, io::Result<Bytes>>
+    where
+        S: Stream<Item = io::Result<Bytes>> + Send + 'static,
+    {
+        ProgressStream {
+            inner: stream.boxed(),
+            progress: self.clone(),
+        }
+        .boxed()
+    }
+}
+
+struct ProgressStream {
+    inner: BoxStream<'static, io::Result<Bytes>>,
+    progress: Progress,
+}
+
+impl Stream for ProgressStream {
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let res = self.inner.poll_next_unpin(cx);
+        match &res {
+            Poll::Ready(Some(_)) => self.progress.touch(),
+            Poll::Ready(None) => self.progress.finish(),
+            Poll::Pending => {}
+        }
+        res
+    }
+}
+
+fn stalled(what: &str, limit: Duration) -> anyhow::Error {
+    let message = format!("{} stalled for {:?}", what, limit);
+    io::Error::new(io::ErrorKind::TimedOut, message).into()
+}
+
+/// Fails the request if the body makes no progress within `read_write`, or the response headers
+/// are not received within `header` after the body is sent.
+pub(crate) async fn watch_stall<F, T>(
+    fut: F,
+    progress: &Progress,
+    read_write: Duration,
+    header: Duration,
+) -> Result<T>
+where
+    F: Future<Output = Result<T>>,
+{
+    let watchdog = async {
+        loop {
+            let (what, limit) = if progress.is_sending() {
+                ("sending request body", read_write)
+            } else {
+                ("waiting for response headers", header)
+            };
+            let deadline = *progress.last.lock().unwrap() + limit;
+            let now = Instant::now();
+            if now >= deadline {
+                return stalled(what, limit);
+            }
+            tokio::time::sleep(deadline - now).await;
+        }
+    };
+    match future::select(Box::pin(fut), Box::pin(watchdog)).await {
+        Either::Left((res, _)) => res,
+        Either::Right((e, _)) => Err(e),
+    }
+}
+
+/// Fails the stream if the next chunk is not received within `limit`.
+pub(crate) fn stall_timeout<S>(stream: S, limit: Duration) -> BoxStream<'static, Result<Bytes>>
+where
+    S: Stream<Item = Result<Bytes>> + Send + 'static,
+{
+    stream::unfold(Some(stream.boxed()), move |state| async move {
+        let mut inner = state?;
+        match tokio::time::timeout(limit, inner.next()).await {
+            Ok(Some(it)) => Some((it, Some(inner))),
+            Ok(None) => None,
+            // the stream ends after the error
+            Err(_) => Some((Err(stalled("reading response body", limit)), None)),
+        }
+    })
+    .boxed()
+}
+
+#[cfg(test)]
+mod test_super {
+    use super::*;
+
+    const LIMIT: Duration = Duration::from_millis(100);
+
+    #[tokio::test]
+    async fn test_watch_stall() {
+        let progress = Progress::new(false);
+        let res = watch_stall(async { Ok(1) }, &progress, LIMIT, LIMIT).await;
+        assert_eq!(1, res.unwrap());
+
+        let slow = async {
+            tokio::time::sleep(LIMIT * 3).await;
+            Ok(())
+        };
+        let e = watch_stall(slow, &progress, LIMIT, LIMIT)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            io::ErrorKind::TimedOut,
+            e.downcast_ref::<io::Error>().unwrap().kind()
+        );
+
+        // the body keeps making progress
+        let progress = Progress::new(true);
+        let chunks = stream::iter(0..6).then(|_| async {
+            tokio::time::sleep(LIMIT / 2).await;
+            Ok(Bytes::from_static(b"x"))
+        });
+        let mut body = progress.wrap(chunks);
+        let send = async {
+            while let Some(it) = body.next().await {
+                it?;
+            }
+            Ok(())
+        };
+        assert!(watch_stall(send, &progress, LIMIT, LIMIT).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_stall_timeout() {
+        let chunks = stream::iter(0..2).then(|i| async move {
+            if i > 0 {
+                tokio::time::sleep(LIMIT * 3).await;
+            }
+            Ok(Bytes::from_static(b"x"))
+        });
+        let res: Vec<_> = stall_timeout(chunks, LIMIT).collect().await;
+        assert_eq!(2, res.len());
+        assert!(res[0].is_ok());
+        assert!(res[1].is_err());
+    }
+}
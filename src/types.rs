@@ -1,56 +1,96 @@
 use std::collections::{BTreeMap, HashMap};
-use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 
 use reqwest::Response;
 
+use crate::body::Body;
+use crate::timeout::Progress;
 use crate::Result;
 
 pub(crate) type Params = BTreeMap<String, Option<String>>;
 pub(crate) type Headers = HashMap<String, String>;
 
+pub(crate) const HEADER_ETAG: &str = "etag";
+pub(crate) const HEADER_OSS_REQUEST_ID: &str = "x-oss-request-id";
+pub(crate) const HEADER_OSS_HASH_CRC64: &str = "x-oss-hash-crc64ecma";
+pub(crate) const HEADER_OSS_VERSION_ID: &str = "x-oss-version-id";
+pub(crate) const HEADER_OSS_META_PREFIX: &str = "x-oss-meta-";
+pub(crate) const HEADER_OSS_TAGGING: &str = "x-oss-tagging";
+pub(crate) const HEADER_OSS_ERR: &str = "x-oss-err";
+
 pub(crate) struct Request {
     pub(crate) url: String,
     pub(crate) method: reqwest::Method,
     pub(crate) headers: Headers,
     pub(crate) params: Params,
-    pub(crate) body: Vec<u8>,
+    pub(crate) body: Body,
+    // the running crc64 of the sent body, only present when crc check is enabled
+    pub(crate) crc: Option<Arc<AtomicU64>>,
+    // the timeout of the whole request, there is no limit if it is absent
+    pub(crate) timeout: Option<Duration>,
+}
+
+/// The options of a request which are not sent to the server.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct RequestOptions {
+    /// The crc64 of the object before the request, the crc64 of the body is checked
+    /// against the server if it is present.
+    pub(crate) init_crc: Option<u64>,
+    /// Overrides the timeout of the whole request.
+    pub(crate) timeout: Option<Duration>,
+    /// Whether the request transfers the content of an object, which is limited by
+    /// `HttpTimeout::long` instead of the timeout of the client.
+    pub(crate) transfer: bool,
+    /// Whether a POST request is safe to be sent again, the other methods are always idempotent.
+    pub(crate) idempotent: bool,
 }
 
 impl Request {
-    pub(crate) async fn send(self, client: &reqwest::Client) -> Result<Response> {
+    /// Clones the request to send it again, `None` if the body cannot be replayed.
+    pub(crate) fn try_clone(&self) -> Option<Request> {
+        Some(Request {
+            url: self.url.clone(),
+            method: self.method.clone(),
+            headers: self.headers.clone(),
+            params: self.params.clone(),
+            body: self.body.try_clone()?,
+            crc: self
+                .crc
+                .as_ref()
+                .map(|it| Arc::new(AtomicU64::new(it.load(Ordering::SeqCst)))),
+            timeout: self.timeout,
+        })
+    }
+
+    pub(crate) async fn send(
+        self,
+        client: &reqwest::Client,
+        progress: &Progress,
+    ) -> Result<Response> {
         let Self {
             url,
             method,
             headers,
             params,
             body,
+            crc,
+            timeout,
         } = self;
 
         let mut req = client.request(method, url);
         for (k, v) in headers {
             req = req.header(&k, &v);
         }
+        if let Some(it) = timeout {
+            req = req.timeout(it);
+        }
 
         if !body.is_empty() {
-            req = req.body(body);
+            req = req.body(body.into_reqwest(crc, progress).await?);
         }
 
         Ok(req.send().await?)
     }
 }
-
-pub(crate) trait Credentials: Send + Sync {
-    fn access_key_id(&self) -> &str;
-    fn access_key_secret(&self) -> &str;
-    fn security_token(&self) -> &str;
-}
-
-impl Debug for dyn Credentials {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("Credentials")
-            .field("access_key_id", &self.access_key_id().to_string())
-            .field("access_key_secret", &self.access_key_secret().to_string())
-            .field("security_token", &self.security_token().to_string())
-            .finish()
-    }
-}
@@ -0,0 +1,475 @@
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+use crate::bucket::Bucket;
+use crate::checkpoint::{self, Checkpoint, FileStat};
+use crate::crc64;
+use crate::error::OSSError;
+use crate::multipart::{self, CompleteMultipartUploadResult, PartInfo, UploadId, MAX_PART_NUMBER};
+use crate::object::PutObjectOptions;
+use crate::Result;
+
+const MIN_PART_SIZE: u64 = 100 * 1024;
+const DEFAULT_PART_SIZE: u64 = 8 * 1024 * 1024;
+const DEFAULT_PARALLEL: usize = 3;
+
+/// Options of `Bucket::upload_file`.
+#[derive(Debug, Clone)]
+pub struct UploadFileOptions {
+    part_size: u64,
+    parallel: usize,
+    checkpoint_dir: Option<PathBuf>,
+    object: PutObjectOptions,
+}
+
+impl Default for UploadFileOptions {
+    fn default() -> Self {
+        UploadFileOptions {
+            part_size: DEFAULT_PART_SIZE,
+            parallel: DEFAULT_PARALLEL,
+            checkpoint_dir: None,
+            object: Default::default(),
+        }
+    }
+}
+
+impl UploadFileOptions {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// The size of each part, at least 100KB. It will be enlarged if the file needs more
+    /// than 10000 parts.
+    pub fn part_size(mut self, part_size: u64) -> Self {
+        self.part_size = part_size.max(MIN_PART_SIZE);
+        self
+    }
+
+    /// The number of parts uploaded at the same time.
+    pub fn parallel(mut self, parallel: usize) -> Self {
+        self.parallel = parallel.max(1);
+        self
+    }
+
+    /// Enables the checkpoint, which is saved in the dir after each part is uploaded.
+    /// A failed upload can be resumed by calling `upload_file` again with the same dir.
+    pub fn checkpoint_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.checkpoint_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Sets the content type, user metadata, storage class, ACL... of the object.
+    pub fn object_options(mut self, options: PutObjectOptions) -> Self {
+        self.object = options;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct UploadPart {
+    number: u32,
+    offset: u64,
+    size: u64,
+    etag: Option<String>,
+    crc64: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct UploadCheckpoint {
+    md5: String,
+    file: PathBuf,
+    file_stat: FileStat,
+    bucket: String,
+    object: String,
+    upload_id: String,
+    parts: Vec<UploadPart>,
+}
+
+impl Checkpoint for UploadCheckpoint {
+    fn md5_mut(&mut self) -> &mut String {
+        &mut self.md5
+    }
+}
+
+impl UploadCheckpoint {
+    fn is_valid(&self, file: &Path, stat: &FileStat, bucket: &str, object: &str) -> bool {
+        self.file == file
+            && &self.file_stat == stat
+            && self.bucket == bucket
+            && self.object == object
+    }
+
+    fn part_infos(&self) -> Vec<PartInfo> {
+        self.parts
+            .iter()
+            .map(|it| PartInfo {
+                part_number: it.number,
+                etag: it.etag.clone().unwrap_or_default(),
+                crc64: it.crc64,
+            })
+            .collect()
+    }
+}
+
+fn split_parts(size: u64, part_size: u64) -> Vec<UploadPart> {
+    // enlarge the part size if there are too many parts
+    let part_size = part_size.max((size + MAX_PART_NUMBER as u64 - 1) / MAX_PART_NUMBER as u64);
+
+    let mut parts = vec![];
+    let mut offset = 0;
+    while offset < size || parts.is_empty() {
+        let n = part_size.min(size - offset);
+        parts.push(UploadPart {
+            number: parts.len() as u32 + 1,
+            offset,
+            size: n,
+            etag: None,
+            crc64: None,
+        });
+        offset += n;
+    }
+    parts
+}
+
+async fn read_part(file: &Path, offset: u64, size: u64) -> Result<Vec<u8>> {
+    let mut f = tokio::fs::File::open(file).await?;
+    f.seek(SeekFrom::Start(offset)).await?;
+    let mut b = vec![0u8; size as usize];
+    f.read_exact(&mut b).await?;
+    Ok(b)
+}
+
+pub(crate) async fn upload_file(
+    bucket: &Bucket,
+    object: &str,
+    file: &Path,
+    options: UploadFileOptions,
+) -> Result<CompleteMultipartUploadResult> {
+    let stat = FileStat::of(file).await?;
+    let cp_path = options
+        .checkpoint_dir
+        .as_ref()
+        .map(|dir| checkpoint::checkpoint_path(dir, file, bucket.name(), object));
+
+    let mut resumed = None;
+    if let Some(path) = &cp_path {
+        if let Some(it) = checkpoint::load::<UploadCheckpoint>(path).await? {
+            if it.is_valid(file, &stat, bucket.name(), object) {
+                info!(
+                    "resume upload {} from checkpoint {}",
+                    it.upload_id,
+                    path.display()
+                );
+                resumed = Some(it);
+            } else {
+                // the file or the target is changed, the previous upload is useless
+                info!("discard outdated checkpoint {}", path.display());
+                let upload_id = UploadId::new(it.upload_id);
+                bucket
+                    .abort_multipart_upload(&it.object, &upload_id)
+                    .await
+                    .ok();
+            }
+        }
+    }
+
+    if let Some(mut cp) = resumed {
+        match upload_parts(bucket, object, file, &options, &mut cp, cp_path.as_deref()).await {
+            Err(e) if multipart::is_no_such_upload(&e) => {
+                // the upload is expired or aborted, the checkpoint can never be resumed
+                info!("upload {} of checkpoint is gone, start over", cp.upload_id);
+                if let Some(path) = &cp_path {
+                    checkpoint::remove(path).await?;
+                }
+            }
+            res => return res,
+        }
+    }
+
+    let upload_id = bucket
+        .initiate_multipart_upload_with_options(object, options.object.clone().into())
+        .await?;
+    let mut cp = UploadCheckpoint {
+        md5: Default::default(),
+        file: file.to_path_buf(),
+        file_stat: stat.clone(),
+        bucket: bucket.name().into(),
+        object: object.into(),
+        upload_id: upload_id.to_string(),
+        parts: split_parts(stat.size, options.part_size),
+    };
+    upload_parts(bucket, object, file, &options, &mut cp, cp_path.as_deref()).await
+}
+
+/// Uploads the pending parts of the checkpoint, then completes the upload.
+async fn upload_parts(
+    bucket: &Bucket,
+    object: &str,
+    file: &Path,
+    options: &UploadFileOptions,
+    cp: &mut UploadCheckpoint,
+    cp_path: Option<&Path>,
+) -> Result<CompleteMultipartUploadResult> {
+    if let Some(path) = cp_path {
+        checkpoint::save(path, cp).await?;
+    }
+
+    let upload_id = UploadId::new(cp.upload_id.clone());
+    let pending: Vec<UploadPart> = cp
+        .parts
+        .iter()
+        .filter(|it| it.etag.is_none())
+        .cloned()
+        .collect();
+
+    let mut uploads = futures::stream::iter(pending)
+        .map(|part| {
+            let upload_id = &upload_id;
+            async move {
+                let b = read_part(file, part.offset, part.size).await?;
+                bucket.upload_part(object, upload_id, part.number, b).await
+            }
+        })
+        .buffer_unordered(options.parallel);
+
+    while let Some(res) = uploads.next().await {
+        let info = match res {
+            Ok(it) => it,
+            Err(e) => {
+                // without checkpoint, the upload can never be resumed
+                if cp_path.is_none() {
+                    bucket.abort_multipart_upload(object, &upload_id).await.ok();
+                }
+                return Err(e);
+            }
+        };
+
+        if let Some(part) = cp.parts.iter_mut().find(|it| it.number == info.part_number) {
+            part.etag = Some(info.etag);
+            part.crc64 = info.crc64;
+        }
+        if let Some(path) = cp_path {
+            checkpoint::save(path, cp).await?;
+        }
+    }
+    drop(uploads);
+
+    let res = match bucket
+        .complete_multipart_upload(object, &upload_id, &cp.part_infos())
+        .await
+    {
+        Ok(it) => it,
+        Err(e) => {
+            if cp_path.is_none() {
+                bucket.abort_multipart_upload(object, &upload_id).await.ok();
+            }
+            return Err(e);
+        }
+    };
+
+    if let Some(expect) = res.crc64 {
+        // the crc64 of the object is combined from the ones returned for the parts
+        let actual = cp.parts.iter().try_fold(0, |acc, it| {
+            it.crc64.map(|crc| crc64::combine(acc, crc, it.size))
+        });
+        match actual {
+            Some(actual) if actual != expect => {
+                // the upload is completed, the checkpoint can never be resumed
+                if let Some(path) = cp_path {
+                    checkpoint::remove(path).await?;
+                }
+                return Err(OSSError::CrcMismatch(actual, expect).into());
+            }
+            _ => (),
+        }
+    }
+
+    if let Some(path) = cp_path {
+        checkpoint::remove(path).await?;
+    }
+
+    Ok(res)
+}
+
+#[cfg(test)]
+mod test_super {
+    use crate::test_support::Request;
+
+    use super::*;
+
+    #[test]
+    fn test_split_parts() {
+        let parts = split_parts(250, 100);
+        assert_eq!(3, parts.len());
+        assert_eq!(
+            (3, 200, 50),
+            (parts[2].number, parts[2].offset, parts[2].size)
+        );
+
+        let parts = split_parts(0, 100);
+        assert_eq!(1, parts.len());
+        assert_eq!(0, parts[0].size);
+
+        let parts = split_parts(MAX_PART_NUMBER as u64 * 10 + 1, 1);
+        assert!(parts.len() <= MAX_PART_NUMBER as usize);
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("upload.txt");
+        tokio::fs::write(&file, b"hello world").await.unwrap();
+
+        let stat = FileStat::of(&file).await.unwrap();
+        let path = checkpoint::checkpoint_path(dir.path(), &file, "bucket", "object");
+        let mut cp = UploadCheckpoint {
+            md5: Default::default(),
+            file: file.clone(),
+            file_stat: stat.clone(),
+            bucket: "bucket".into(),
+            object: "object".into(),
+            upload_id: "upload".into(),
+            parts: split_parts(stat.size, MIN_PART_SIZE),
+        };
+        checkpoint::save(&path, &mut cp).await.unwrap();
+
+        let loaded = checkpoint::load::<UploadCheckpoint>(&path)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(loaded.is_valid(&file, &stat, "bucket", "object"));
+        assert!(!loaded.is_valid(&file, &stat, "bucket", "other"));
+
+        // tampered checkpoint should be ignored
+        let tampered = tokio::fs::read_to_string(&path)
+            .await
+            .unwrap()
+            .replace("upload", "other");
+        tokio::fs::write(&path, tampered).await.unwrap();
+        assert!(checkpoint::load::<UploadCheckpoint>(&path)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    type Response = (u16, Vec<(String, String)>, String);
+
+    // stands in for the multipart upload of OSS, `complete` answers the completion
+    fn multipart_handler(
+        complete: impl Fn(&Request) -> Response + Send + Sync + 'static,
+    ) -> impl Fn(&Request) -> Response + Send + Sync + 'static {
+        move |req| match req.method.as_str() {
+            "POST" if req.path.ends_with("?uploads") => {
+                let xml = "<InitiateMultipartUploadResult><UploadId>new</UploadId>\
+                           </InitiateMultipartUploadResult>";
+                (200, vec![], xml.into())
+            }
+            "PUT" => (200, vec![("etag".into(), "\"etag\"".into())], "".into()),
+            "POST" => complete(req),
+            _ => (204, vec![], "".into()),
+        }
+    }
+
+    fn error(status: u16, code: &str) -> Response {
+        let xml = format!("<Error><Code>{}</Code><Message></Message></Error>", code);
+        (status, vec![], xml)
+    }
+
+    #[tokio::test]
+    async fn test_abort_on_complete_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("upload.txt");
+        tokio::fs::write(&file, b"hello world").await.unwrap();
+
+        let handler = multipart_handler(|_| error(500, "InternalError"));
+        let (endpoint, requests) = crate::test_support::serve_with_headers(handler).await;
+        let bucket = crate::test_support::bucket(&endpoint);
+        upload_file(&bucket, "a.txt", &file, Default::default())
+            .await
+            .unwrap_err();
+
+        // the upload without checkpoint can never be resumed
+        let requests = requests.lock().unwrap();
+        let last = requests.last().unwrap();
+        assert_eq!("DELETE", last.method);
+        assert!(last.path.contains("uploadId=new"), "{}", last.path);
+    }
+
+    #[tokio::test]
+    async fn test_restart_gone_upload() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("upload.txt");
+        tokio::fs::write(&file, b"hello world").await.unwrap();
+
+        let stat = FileStat::of(&file).await.unwrap();
+        let path = checkpoint::checkpoint_path(dir.path(), &file, "bucket", "a.txt");
+        let mut cp = UploadCheckpoint {
+            md5: Default::default(),
+            file: file.clone(),
+            file_stat: stat.clone(),
+            bucket: "bucket".into(),
+            object: "a.txt".into(),
+            upload_id: "old".into(),
+            parts: split_parts(stat.size, DEFAULT_PART_SIZE),
+        };
+        checkpoint::save(&path, &mut cp).await.unwrap();
+
+        let complete = |_: &Request| {
+            let xml = "<CompleteMultipartUploadResult><ETag>\"etag\"</ETag>\
+                       </CompleteMultipartUploadResult>";
+            (200, vec![], xml.into())
+        };
+        let multipart = multipart_handler(complete);
+        let handler = move |req: &Request| {
+            if req.path.contains("uploadId=old") {
+                error(404, "NoSuchUpload")
+            } else {
+                multipart(req)
+            }
+        };
+        let (endpoint, _) = crate::test_support::serve_with_headers(handler).await;
+        let bucket = crate::test_support::bucket(&endpoint);
+        let options = UploadFileOptions::new().checkpoint_dir(dir.path());
+        upload_file(&bucket, "a.txt", &file, options).await.unwrap();
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_remove_checkpoint_on_crc_mismatch() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("upload.txt");
+        tokio::fs::write(&file, b"hello world").await.unwrap();
+
+        let crc = crc64::checksum(b"hello world");
+        let complete = move |_: &Request| {
+            let headers = vec![("x-oss-hash-crc64ecma".into(), (crc + 1).to_string())];
+            let xml = "<CompleteMultipartUploadResult><ETag>\"etag\"</ETag>\
+                       </CompleteMultipartUploadResult>";
+            (200, headers, xml.into())
+        };
+        let multipart = multipart_handler(complete);
+        let handler = move |req: &Request| {
+            let (status, mut headers, body) = multipart(req);
+            if req.method == "PUT" {
+                headers.push(("x-oss-hash-crc64ecma".into(), crc.to_string()));
+            }
+            (status, headers, body)
+        };
+        let (endpoint, _) = crate::test_support::serve_with_headers(handler).await;
+        let bucket = crate::test_support::bucket(&endpoint);
+        let options = UploadFileOptions::new().checkpoint_dir(dir.path());
+        let e = upload_file(&bucket, "a.txt", &file, options)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            e.downcast_ref::<OSSError>(),
+            Some(OSSError::CrcMismatch(..))
+        ));
+        let path = checkpoint::checkpoint_path(dir.path(), &file, "bucket", "a.txt");
+        assert!(!path.exists());
+    }
+}
@@ -1,5 +1,6 @@
 use std::io::Write;
 
+use base64::{engine::general_purpose::STANDARD, Engine as _};
 use once_cell::sync::Lazy;
 
 use crate::Result;
@@ -35,8 +36,76 @@ pub(crate) fn query_escape(input: &str) -> String {
     s[2..].replace('+', "%20")
 }
 
+/// Encodes a string as RFC 3986, all characters except the unreserved ones are escaped.
+/// The '/' is kept if `keep_slash` is true, which is used to encode a path.
+pub(crate) fn uri_encode(input: &str, keep_slash: bool) -> String {
+    let mut buf = String::with_capacity(input.len());
+    for b in input.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                buf.push(b as char)
+            }
+            b'/' if keep_slash => buf.push('/'),
+            _ => buf.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    buf
+}
+
+/// Returns the region of an endpoint, e.g. `cn-hangzhou` of `oss-cn-hangzhou.aliyuncs.com`.
+pub(crate) fn region_of_endpoint(endpoint: &str) -> Option<String> {
+    let host = endpoint
+        .split("://")
+        .last()
+        .unwrap_or_default()
+        .split(|c| c == '/' || c == ':')
+        .next()
+        .unwrap_or_default();
+    if !host.ends_with(".aliyuncs.com") {
+        return None;
+    }
+    let region = host.split('.').next()?.strip_prefix("oss-")?;
+    let region = region.strip_suffix("-internal").unwrap_or(region);
+    Some(region.to_string())
+}
+
+/// Encodes the tags as a query string, which is the format of 'x-oss-tagging'.
+pub(crate) fn tagging_str<'a, I>(tags: I) -> String
+where
+    I: IntoIterator<Item = (&'a String, &'a String)>,
+{
+    tags.into_iter()
+        .map(|(k, v)| format!("{}={}", query_escape(k), query_escape(v)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Returns the base64 encoded md5 digest, which is the format of 'Content-MD5'.
+pub(crate) fn content_md5(b: &[u8]) -> String {
+    STANDARD.encode(md5::compute(b).0)
+}
+
+/// Decodes a string in the 'application/x-www-form-urlencoded' format.
+pub(crate) fn url_decode(input: &str) -> Result<String> {
+    let mut kvs: Vec<(String, String)> = serde_urlencoded::from_str(&format!("k={}", input))?;
+    match kvs.pop() {
+        Some((_, v)) => Ok(v),
+        None => Ok(Default::default()),
+    }
+}
+
 pub(crate) fn httptime() -> String {
-    format!("{}", chrono::Utc::now().format("%a, %d %b %Y %H:%M:%S GMT"))
+    format_httptime(&chrono::Utc::now())
+}
+
+pub(crate) fn format_httptime(t: &chrono::DateTime<chrono::Utc>) -> String {
+    format!("{}", t.format("%a, %d %b %Y %H:%M:%S GMT"))
+}
+
+pub(crate) fn parse_httptime(s: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    chrono::DateTime::parse_from_rfc2822(s)
+        .ok()
+        .map(|it| it.with_timezone(&chrono::Utc))
 }
 
 pub(crate) struct SysInfo(String, String, String);
@@ -99,8 +168,52 @@ mod test_super {
         );
     }
 
+    #[test]
+    fn test_httptime() {
+        let t = parse_httptime("Wed, 21 Oct 2015 07:28:00 GMT").unwrap();
+        assert_eq!("Wed, 21 Oct 2015 07:28:00 GMT", format_httptime(&t));
+    }
+
+    #[test]
+    fn test_content_md5() {
+        assert_eq!("XrY7u+Ae7tCTyyK7j1rNww==", content_md5(b"hello world"));
+    }
+
+    #[test]
+    fn test_url_decode() {
+        assert_eq!("a/b c+d", url_decode("a%2Fb+c%2Bd").unwrap());
+        assert_eq!("", url_decode("").unwrap());
+    }
+
     #[test]
     fn test_query_escape() {
         println!("{}", query_escape("abc"));
     }
+
+    #[test]
+    fn test_uri_encode() {
+        assert_eq!("a%20b/c~%2A", uri_encode("a b/c~*", true));
+        assert_eq!("a%20b%2Fc", uri_encode("a b/c", false));
+    }
+
+    #[test]
+    fn test_region_of_endpoint() {
+        assert_eq!(
+            Some("cn-hangzhou".to_string()),
+            region_of_endpoint("https://oss-cn-hangzhou.aliyuncs.com")
+        );
+        assert_eq!(
+            Some("cn-beijing".to_string()),
+            region_of_endpoint("oss-cn-beijing-internal.aliyuncs.com")
+        );
+        assert_eq!(None, region_of_endpoint("http://127.0.0.1:8080"));
+    }
+
+    #[test]
+    fn test_tagging_str() {
+        let mut tags = std::collections::BTreeMap::new();
+        tags.insert("a".to_string(), "1".to_string());
+        tags.insert("b c".to_string(), "x&y".to_string());
+        assert_eq!("a=1&b%20c=x%26y", tagging_str(&tags));
+    }
 }
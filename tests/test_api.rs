@@ -37,3 +37,256 @@ async fn test_get_object() {
         Err(e) => error!("{}", e),
     }
 }
+
+fn new_bucket() -> yunoss::Bucket {
+    let get_env = |k: &str| -> String { std::env::var(k).unwrap_or_default() };
+
+    let cli = Client::builder()
+        .endpoint(get_env("OSS_ENDPOINT"))
+        .credentials_provider(yunoss::EnvCredentials::new())
+        .build()
+        .unwrap();
+    cli.bucket(get_env("OSS_BUCKET")).unwrap()
+}
+
+#[tokio::test]
+async fn test_put_object() {
+    init();
+
+    let bucket = new_bucket();
+    let opts = yunoss::PutObjectOptions::new()
+        .content_type("text/plain")
+        .meta("from", "yunoss");
+    let result = bucket
+        .put_object_with_options("yunoss-test/put_object.txt", "hello world", opts)
+        .await;
+
+    match result {
+        Ok(res) => info!("put object ok: {:?}", res),
+        Err(e) => error!("{}", e),
+    }
+}
+
+#[tokio::test]
+async fn test_append_object() {
+    init();
+
+    let bucket = new_bucket();
+    let object = "yunoss-test/append_object.log";
+    bucket.delete_object(object).await.ok();
+
+    let mut writer = bucket.append_writer(object);
+    for line in ["hello\n", "world\n"] {
+        match writer.append(line).await {
+            Ok(res) => info!("append object ok: {:?}", res),
+            Err(e) => error!("{}", e),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_copy_object() {
+    init();
+
+    let bucket = new_bucket();
+    let source = "yunoss-test/copy_source.txt";
+    if let Err(e) = bucket.put_object(source, "hello world").await {
+        error!("{}", e);
+        return;
+    }
+
+    let opts = yunoss::CopyObjectOptions::new()
+        .metadata_directive(yunoss::CopyDirective::Replace)
+        .object_options(yunoss::PutObjectOptions::new().content_type("text/plain"));
+    match bucket
+        .copy_object_with_options(source, "yunoss-test/copy_target.txt", opts)
+        .await
+    {
+        Ok(res) => info!("copy object ok: {:?}", res),
+        Err(e) => error!("{}", e),
+    }
+}
+
+#[tokio::test]
+async fn test_presign_url() {
+    init();
+
+    let bucket = new_bucket();
+    let object = "yunoss-test/presign.txt";
+    let opts = yunoss::PresignOptions::new().content_type("text/plain");
+    let url = match bucket
+        .presign_url(
+            reqwest::Method::PUT,
+            object,
+            std::time::Duration::from_secs(60),
+            opts,
+        )
+        .await
+    {
+        Ok(it) => it,
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+    info!("presigned url: {}", url);
+
+    let result = reqwest::Client::new()
+        .put(url)
+        .header("content-type", "text/plain")
+        .body("hello world")
+        .send()
+        .await;
+    match result {
+        Ok(res) => info!("put by presigned url: {}", res.status()),
+        Err(e) => error!("{}", e),
+    }
+}
+
+#[tokio::test]
+async fn test_get_object_stream() {
+    use futures::StreamExt;
+
+    init();
+
+    let bucket = new_bucket();
+    let object = std::env::var("OSS_OBJECT").unwrap_or_default();
+
+    match bucket.get_object_stream(object).await {
+        Ok(res) => {
+            info!("content-length: {:?}", res.content_length());
+            let mut stream = res.into_stream();
+            let mut n = 0;
+            while let Some(chunk) = stream.next().await {
+                n += chunk.unwrap().len();
+            }
+            info!("read {} bytes", n);
+        }
+        Err(e) => error!("{}", e),
+    }
+}
+
+#[tokio::test]
+async fn test_delete_objects() {
+    init();
+
+    let bucket = new_bucket();
+    let prefix = "yunoss-test/delete/";
+
+    for i in 0..3 {
+        let key = format!("{}{}.txt", prefix, i);
+        if let Err(e) = bucket.put_object(&key, "hello world").await {
+            error!("{}", e);
+            return;
+        }
+    }
+
+    match bucket.delete_object(format!("{}0.txt", prefix)).await {
+        Ok(()) => info!("delete object ok"),
+        Err(e) => error!("{}", e),
+    }
+
+    match bucket.delete_prefix(prefix, 2).await {
+        Ok(n) => info!("delete {} objects by prefix", n),
+        Err(e) => error!("{}", e),
+    }
+}
+
+#[tokio::test]
+async fn test_list_all() {
+    use futures::TryStreamExt;
+
+    init();
+
+    let bucket = new_bucket();
+    let opts = yunoss::ListObjectsV2Options::new()
+        .delimiter("/")
+        .max_keys(10);
+
+    match bucket.list_all(opts).try_collect::<Vec<_>>().await {
+        Ok(entries) => {
+            for it in entries {
+                info!("{:?}", it);
+            }
+        }
+        Err(e) => error!("{}", e),
+    }
+}
+
+#[tokio::test]
+async fn test_multipart_upload() {
+    init();
+
+    let bucket = new_bucket();
+    let object = "yunoss-test/multipart.txt";
+
+    let upload_id = match bucket.initiate_multipart_upload(object).await {
+        Ok(it) => it,
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+
+    let part = bucket
+        .upload_part(object, &upload_id, 1, "hello world")
+        .await
+        .unwrap();
+    let listed = bucket
+        .list_parts(object, &upload_id, Default::default())
+        .await
+        .unwrap();
+    assert_eq!(1, listed.parts.len());
+
+    let res = bucket
+        .complete_multipart_upload(object, &upload_id, &[part])
+        .await
+        .unwrap();
+    info!("complete multipart upload: {:?}", res);
+}
+
+#[tokio::test]
+async fn test_upload_file() {
+    init();
+
+    let bucket = new_bucket();
+    let dir = std::env::temp_dir().join("yunoss-test-upload");
+    let file = dir.join("upload.bin");
+    tokio::fs::create_dir_all(&dir).await.unwrap();
+    tokio::fs::write(&file, vec![7u8; 300 * 1024])
+        .await
+        .unwrap();
+
+    let opts = yunoss::UploadFileOptions::new()
+        .part_size(100 * 1024)
+        .parallel(2)
+        .checkpoint_dir(&dir);
+    match bucket
+        .upload_file("yunoss-test/upload.bin", &file, opts)
+        .await
+    {
+        Ok(res) => info!("upload file ok: {:?}", res),
+        Err(e) => error!("{}", e),
+    }
+}
+
+#[tokio::test]
+async fn test_download_file() {
+    init();
+
+    let bucket = new_bucket();
+    let object = std::env::var("OSS_OBJECT").unwrap_or_default();
+    let dir = std::env::temp_dir().join("yunoss-test-download");
+    tokio::fs::create_dir_all(&dir).await.unwrap();
+
+    let opts = yunoss::DownloadFileOptions::new()
+        .parallel(2)
+        .checkpoint_dir(&dir);
+    match bucket
+        .download_file(object, dir.join("download.bin"), opts)
+        .await
+    {
+        Ok(meta) => info!("download file ok: {:?}", meta),
+        Err(e) => error!("{}", e),
+    }
+}